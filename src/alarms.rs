@@ -0,0 +1,359 @@
+use crate::fan_data::{FanData, Reading};
+use crate::sqlite::Device;
+use crate::staleness::{Staleness, StalenessPolicy};
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::fs;
+
+// Rules used when no alarm file is given on the command line
+const DEFAULT_ALARMS: &str = r#"
+[[alarm]]
+name = "VSD Error"
+severity = "critical"
+condition = "vsd_error == true"
+
+[[alarm]]
+name = "Low Battery"
+severity = "warning"
+condition = "battery_voltage < 11.8"
+
+[[alarm]]
+name = "High Vibration"
+severity = "critical"
+condition = "motor_vibration > 7.1"
+
+[[alarm]]
+name = "Offline"
+severity = "warning"
+condition = "offline == true"
+
+[[alarm]]
+name = "Door Open While Running"
+severity = "warning"
+condition = "door_open == true && vsd_running == true"
+"#;
+
+// Fields that can be used in alarm conditions
+const ALARM_FIELDS: [&str; 23] = [
+    "vsd_error",
+    "vsd_running",
+    "vsd_command",
+    "main_panel_open",
+    "control_door",
+    "main_switch",
+    "door_open",
+    "temperature_top",
+    "temperature_bottom",
+    "temperature_far",
+    "signal_strength",
+    "control_method",
+    "battery_voltage",
+    "motor_vibration",
+    "motor_current",
+    "humidity",
+    "wind_speed",
+    "wind_direction",
+    "rain_meter",
+    "minutes_since_update",
+    // At least late, at least stale and offline by the device's staleness policy
+    "late",
+    "stale",
+    "offline",
+];
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum AlarmSeverity {
+    Warning,
+    Critical,
+}
+
+impl AlarmSeverity {
+    pub fn label(&self) -> &str {
+        match self {
+            AlarmSeverity::Warning => "Warning",
+            AlarmSeverity::Critical => "Critical",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AlarmFile {
+    #[serde(default, rename = "alarm")]
+    alarms: Vec<AlarmRuleConfig>,
+}
+
+#[derive(Deserialize)]
+struct AlarmRuleConfig {
+    name: String,
+    #[serde(default = "default_severity")]
+    severity: AlarmSeverity,
+    condition: String,
+}
+
+fn default_severity() -> AlarmSeverity {
+    AlarmSeverity::Warning
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Debug)]
+struct Comparison {
+    field: String,
+    operator: Operator,
+    value: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct AlarmRule {
+    pub name: String,
+    pub severity: AlarmSeverity,
+    // All comparisons must hold for the alarm to be active
+    comparisons: Vec<Comparison>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ActiveAlarm {
+    pub fan_index: usize,
+    pub name: String,
+    pub severity: AlarmSeverity,
+}
+
+pub fn load_alarm_rules(path: Option<&str>) -> Result<Vec<AlarmRule>, String> {
+    let contents = match path {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|error| format!("Failed reading alarm file {path}: {error}"))?,
+        None => String::from(DEFAULT_ALARMS),
+    };
+    parse_alarm_rules(&contents)
+}
+
+pub fn parse_alarm_rules(contents: &str) -> Result<Vec<AlarmRule>, String> {
+    let alarm_file: AlarmFile =
+        toml::from_str(contents).map_err(|error| format!("Invalid alarm file: {error}"))?;
+    let mut rules = Vec::new();
+    for config in alarm_file.alarms {
+        let comparisons = parse_condition(&config.condition)
+            .map_err(|error| format!("Alarm \"{}\": {error}", config.name))?;
+        rules.push(AlarmRule {
+            name: config.name,
+            severity: config.severity,
+            comparisons,
+        });
+    }
+    Ok(rules)
+}
+
+// Parses conditions such as "door_open == true && vsd_running == true"
+fn parse_condition(condition: &str) -> Result<Vec<Comparison>, String> {
+    let mut comparisons = Vec::new();
+    for part in condition.split("&&") {
+        let part = part.trim();
+        let operators = [
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessOrEqual),
+            (">=", Operator::GreaterOrEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+        ];
+        let (position, symbol, operator) = match operators
+            .iter()
+            .find_map(|(symbol, operator)| part.find(symbol).map(|p| (p, *symbol, *operator)))
+        {
+            Some(found) => found,
+            None => return Err(format!("no comparison operator in \"{part}\"")),
+        };
+        let field = part[..position].trim().to_string();
+        let value = part[position + symbol.len()..].trim();
+        if !ALARM_FIELDS.contains(&field.as_str()) {
+            return Err(format!("unknown field \"{field}\""));
+        }
+        let value = match value {
+            "true" => 1.0,
+            "false" => 0.0,
+            number => number
+                .parse::<f64>()
+                .map_err(|_| format!("invalid value \"{number}\""))?,
+        };
+        comparisons.push(Comparison {
+            field,
+            operator,
+            value,
+        });
+    }
+    Ok(comparisons)
+}
+
+// Returns the value of a rule field, or None when there is no valid reading
+fn get_field_value(field: &str, dta: &FanData, policy: &StalenessPolicy) -> Option<f64> {
+    let bool_value = |value: bool| if value { 1.0 } else { 0.0 };
+    let reading = |reading: Reading<f32>| reading.value().map(f64::from);
+    let staleness = dta.get_staleness(policy).ok();
+    match field {
+        "vsd_error" => dta.vsd_error.map(bool_value),
+        "vsd_running" => dta.vsd_running.map(bool_value),
+        "vsd_command" => dta.vsd_command.map(bool_value),
+        "main_panel_open" => dta.main_panel_open.map(bool_value),
+        "control_door" => dta.control_door.map(bool_value),
+        "main_switch" => dta.main_switch.map(bool_value),
+        // Door fields report true when the door is closed, doors the device
+        // doesn't report are left out
+        "door_open" => match (dta.main_panel_open, dta.control_door) {
+            (Some(false), _) | (_, Some(false)) => Some(1.0),
+            (None, None) => None,
+            _ => Some(0.0),
+        },
+        "temperature_top" => reading(dta.temperature_top),
+        "temperature_bottom" => reading(dta.temperature_bottom),
+        "temperature_far" => reading(dta.temperature_far),
+        "signal_strength" => reading(dta.signal_strength),
+        "control_method" => Some(dta.control_method as f64),
+        "battery_voltage" => reading(dta.battery_voltage),
+        "motor_vibration" => reading(dta.motor_vibration),
+        "motor_current" => reading(dta.motor_current),
+        "humidity" => reading(dta.humidity),
+        "wind_speed" => reading(dta.wind_speed),
+        "wind_direction" => dta.wind_direction.value().map(f64::from),
+        "rain_meter" => reading(dta.rain_meter),
+        "minutes_since_update" => dta
+            .get_seconds_since_last()
+            .ok()
+            .map(|seconds| seconds as f64 / 60.0),
+        "late" => staleness.map(|staleness| bool_value(staleness >= Staleness::Late)),
+        "stale" => staleness.map(|staleness| bool_value(staleness >= Staleness::Stale)),
+        "offline" => staleness.map(|staleness| bool_value(staleness == Staleness::Offline)),
+        _ => None,
+    }
+}
+
+impl AlarmRule {
+    pub fn is_active(&self, fan_data: &FanData, policy: &StalenessPolicy) -> bool {
+        self.comparisons.iter().all(|comparison| {
+            let value = match get_field_value(&comparison.field, fan_data, policy) {
+                Some(value) => value,
+                None => return false,
+            };
+            match comparison.operator {
+                Operator::Equal => value == comparison.value,
+                Operator::NotEqual => value != comparison.value,
+                Operator::Less => value < comparison.value,
+                Operator::LessOrEqual => value <= comparison.value,
+                Operator::Greater => value > comparison.value,
+                Operator::GreaterOrEqual => value >= comparison.value,
+            }
+        })
+    }
+}
+
+// Evaluates every rule against the latest data of every device
+pub fn evaluate_alarms(
+    rules: &[AlarmRule],
+    devices: &[Device],
+    overview: &[Option<FanData>],
+) -> Vec<ActiveAlarm> {
+    let mut alarms = Vec::new();
+    for (fan_index, (device, fan_data)) in devices.iter().zip(overview).enumerate() {
+        let fan_data = match fan_data {
+            Some(dta) => dta,
+            None => continue,
+        };
+        for rule in rules {
+            if rule.is_active(fan_data, &device.staleness) {
+                alarms.push(ActiveAlarm {
+                    fan_index,
+                    name: rule.name.clone(),
+                    severity: rule.severity,
+                });
+            }
+        }
+    }
+    // Critical alarms first
+    alarms.sort_by_key(|alarm| Reverse(alarm.severity));
+    alarms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_comparisons_joined_by_and() {
+        let comparisons = parse_condition("door_open == true && battery_voltage < 11.8").unwrap();
+        assert_eq!(comparisons.len(), 2);
+        assert_eq!(comparisons[0].field, "door_open");
+        assert_eq!(comparisons[0].operator, Operator::Equal);
+        assert_eq!(comparisons[0].value, 1.0);
+        assert_eq!(comparisons[1].field, "battery_voltage");
+        assert_eq!(comparisons[1].operator, Operator::Less);
+        assert_eq!(comparisons[1].value, 11.8);
+    }
+
+    #[test]
+    fn parses_two_character_operators_before_one_character_ones() {
+        let operators = [
+            ("humidity == 1", Operator::Equal),
+            ("humidity != 1", Operator::NotEqual),
+            ("humidity <= 1", Operator::LessOrEqual),
+            ("humidity >= 1", Operator::GreaterOrEqual),
+            ("humidity < 1", Operator::Less),
+            ("humidity > 1", Operator::Greater),
+        ];
+        for (condition, operator) in operators {
+            let comparisons = parse_condition(condition).unwrap();
+            assert_eq!(comparisons[0].operator, operator, "{condition}");
+            assert_eq!(comparisons[0].value, 1.0, "{condition}");
+        }
+    }
+
+    #[test]
+    fn parses_booleans_and_negative_numbers() {
+        let comparisons = parse_condition("offline == false && signal_strength<-100").unwrap();
+        assert_eq!(comparisons[0].value, 0.0);
+        assert_eq!(comparisons[1].field, "signal_strength");
+        assert_eq!(comparisons[1].value, -100.0);
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        assert_eq!(
+            parse_condition("humidity").unwrap_err(),
+            "no comparison operator in \"humidity\""
+        );
+        assert_eq!(
+            parse_condition("pressure > 3").unwrap_err(),
+            "unknown field \"pressure\""
+        );
+        assert_eq!(
+            parse_condition("humidity > high").unwrap_err(),
+            "invalid value \"high\""
+        );
+        assert!(parse_condition("humidity > 3 && ").is_err());
+    }
+
+    #[test]
+    fn names_the_alarm_with_an_invalid_condition() {
+        let contents = r#"
+[[alarm]]
+name = "Dry"
+condition = "humidity <"
+"#;
+        let error = parse_alarm_rules(contents).unwrap_err();
+        assert_eq!(error, "Alarm \"Dry\": invalid value \"\"");
+    }
+
+    #[test]
+    fn default_rules_parse() {
+        let rules = parse_alarm_rules(DEFAULT_ALARMS).unwrap();
+        assert_eq!(rules.len(), 5);
+        assert_eq!(rules[0].severity, AlarmSeverity::Critical);
+    }
+}
@@ -1,77 +1,814 @@
-use crate::fan_data::FanData;
-use crate::sqlite::{Database, Fan};
-use chrono::{DateTime, Utc};
+use crate::alarms::{evaluate_alarms, ActiveAlarm, AlarmRule, AlarmSeverity};
+use crate::config::Config;
+use crate::device::{DeviceType, Layout};
+use crate::export::{export_snapshot, get_default_export_path, ExportFormat};
+use crate::fan_data::{FanData, FieldInfo, DATETIME_FORMAT};
+use crate::frost::{detect_frost_events, FrostEvent};
+use crate::group::{get_groups, Group};
+use crate::maintenance::MaintenanceReport;
+use crate::search::Search;
+use crate::sqlite::{Database, Device};
+use crate::staleness::StalenessPolicy;
+use crate::status::{get_device_severity, Severity};
+use crate::wind::{get_wind_rose, WindRose};
+use crate::worker::{Request, Response, Worker};
+use chrono::{DateTime, Duration, Utc};
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::time::Instant;
+use tui::layout::Rect;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum View {
+    Overview,
+    // Overview of one group at a time, with its aggregates
+    Groups,
+    // Every device by time since its last report, the longest silent first
+    Watchdog,
+    Detail,
+    History,
+    Maintenance,
+}
+
+// Days listed in the maintenance view
+const MAINTENANCE_DAYS: usize = 14;
+
+// How long a device's tab is highlighted after new rows arrived
+const UPDATE_FLASH_DURATION: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryWindow {
+    OneHour,
+    SixHours,
+    OneDay,
+    SevenDays,
+}
+
+impl HistoryWindow {
+    pub fn duration(&self) -> Duration {
+        match self {
+            HistoryWindow::OneHour => Duration::hours(1),
+            HistoryWindow::SixHours => Duration::hours(6),
+            HistoryWindow::OneDay => Duration::hours(24),
+            HistoryWindow::SevenDays => Duration::days(7),
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            HistoryWindow::OneHour => "1h",
+            HistoryWindow::SixHours => "6h",
+            HistoryWindow::OneDay => "24h",
+            HistoryWindow::SevenDays => "7d",
+        }
+    }
+
+    pub fn next(&self) -> HistoryWindow {
+        match self {
+            HistoryWindow::OneHour => HistoryWindow::SixHours,
+            HistoryWindow::SixHours => HistoryWindow::OneDay,
+            HistoryWindow::OneDay => HistoryWindow::SevenDays,
+            HistoryWindow::SevenDays => HistoryWindow::OneHour,
+        }
+    }
+
+    // Next shorter window, stopping at the shortest
+    pub fn shorter(&self) -> HistoryWindow {
+        match self {
+            HistoryWindow::OneHour | HistoryWindow::SixHours => HistoryWindow::OneHour,
+            HistoryWindow::OneDay => HistoryWindow::SixHours,
+            HistoryWindow::SevenDays => HistoryWindow::OneDay,
+        }
+    }
+
+    // Next longer window, stopping at the longest
+    pub fn longer(&self) -> HistoryWindow {
+        match self {
+            HistoryWindow::OneHour => HistoryWindow::SixHours,
+            HistoryWindow::SixHours => HistoryWindow::OneDay,
+            HistoryWindow::OneDay | HistoryWindow::SevenDays => HistoryWindow::SevenDays,
+        }
+    }
+}
+
+// Widget on screen that reacts to the mouse
+#[derive(Clone, Copy)]
+pub enum MouseTarget {
+    Tab(usize),
+    GroupTab(usize),
+    // Value block of a numeric field, opens its history chart
+    Metric(&'static FieldInfo),
+    Chart,
+    Detail,
+}
+
 pub struct App {
     // pub titles: Vec<&'a str>,
     pub index: usize,
-    pub database: Database,
+    worker: Worker,
+    // Requests sent to the worker that haven't been answered yet
+    pending: usize,
+    // When the worker last reported new rows for each device
+    updated_at: Vec<Option<Instant>>,
     pub fan_data: Option<FanData>,
-    pub fans: Vec<Fan>,
+    pub devices: Vec<Device>,
+    // Latest data of every device, in the same order as devices
+    pub overview: Vec<Option<FanData>>,
+    // Groups of the devices, from the fan_groups table or the config file
+    pub groups: Vec<Group>,
+    pub group_index: usize,
+    pub view: View,
+    pub history_window: HistoryWindow,
+    pub history: Vec<FanData>,
+    // Frost events of the selected device within the history window
+    pub frost_events: Vec<FrostEvent>,
+    pub frost_threshold: f32,
+    // Draw without colors
+    pub monochrome: bool,
+    // Theme and detail view panels
+    pub config: Config,
+    // Panel rows scrolled past when the detail view doesn't fit the terminal
+    pub detail_scroll: usize,
+    // Furthest the detail view could scroll at the last draw, set while drawing
+    pub detail_overflow: Cell<usize>,
+    // Areas of the widgets under the mouse at the last draw, set while drawing
+    pub mouse_targets: RefCell<Vec<(Rect, MouseTarget)>>,
+    // Field charted on its own in the history view, instead of the layout's charts
+    pub history_field: Option<&'static FieldInfo>,
+    // Key bindings popup
+    pub show_help: bool,
+    // Device palette, filtering the tab bar while open
+    pub search: Option<Search>,
+    // Wind readings of the selected device within the history window
+    pub wind_rose: WindRose,
+    // Whether the compass shows the wind rose instead of the current heading
+    pub show_wind_rose: bool,
+    // Run hours of the selected device, only kept up to date in the maintenance view
+    pub maintenance: Option<MaintenanceReport>,
+    pub alarms: Vec<ActiveAlarm>,
+    // Last database error, shown in the UI since stdout is unusable in raw mode
+    pub error: Option<String>,
+    // Result of the last user action, such as an export
+    pub message: Option<String>,
+    alarm_rules: Vec<AlarmRule>,
     last_change: DateTime<Utc>,
 }
 
 impl App {
-    pub fn new(database_path: String) -> Result<App, rusqlite::Error> {
+    pub fn new(
+        database_path: String,
+        alarm_rules: Vec<AlarmRule>,
+        frost_threshold: f32,
+        monochrome: bool,
+        config: Config,
+    ) -> Result<App, rusqlite::Error> {
         let database = Database::new(&database_path)?;
-        let fans = match database.get_fans() {
-            Ok(fans) => fans,
-            Err(error) => {
-                let error_string = error.to_string();
-                println!("Error: {}", { error_string });
-                panic!("Failed fetching fans")
-            }
-        };
-        let fan_data = match database.get_last_fan_data(1) {
-            Ok(data) => data,
-            Err(error) => {
-                println!("{error:?}");
-                None
-            }
-        };
-        Ok(App {
-            fans,
+        let mut devices = database.get_devices()?;
+        config.apply_to_devices(&mut devices);
+        let groups = get_groups(&devices);
+        let mut app = App {
+            devices,
+            groups,
+            group_index: 0,
             index: 0,
-            database,
-            fan_data,
+            worker: Worker::spawn(database),
+            pending: 0,
+            updated_at: Vec::new(),
+            fan_data: None,
+            overview: Vec::new(),
+            view: View::Overview,
+            history_window: HistoryWindow::SixHours,
+            history: Vec::new(),
+            frost_events: Vec::new(),
+            frost_threshold,
+            monochrome,
+            config,
+            detail_scroll: 0,
+            detail_overflow: Cell::new(0),
+            mouse_targets: RefCell::new(Vec::new()),
+            history_field: None,
+            show_help: false,
+            search: None,
+            wind_rose: WindRose::default(),
+            show_wind_rose: false,
+            maintenance: None,
+            alarms: Vec::new(),
+            error: None,
+            message: None,
+            alarm_rules,
             last_change: chrono::offset::Utc::now(),
-        })
+        };
+        app.update_overview();
+        Ok(app)
     }
 
     fn on_change(&mut self) {
         self.last_change = chrono::offset::Utc::now();
+        // Show the overview reading of the new device until its own data arrives
+        self.fan_data = self.overview.get(self.index).cloned().flatten();
+        self.history = Vec::new();
+        self.frost_events = Vec::new();
+        self.wind_rose = WindRose::default();
+        self.maintenance = None;
+        self.detail_scroll = 0;
+        if self.view == View::Maintenance && !self.is_fan_selected() {
+            self.view = View::Detail;
+        }
         self.update_fan_data();
     }
 
+    // New readings are pushed by the worker as they are written, only the
+    // alarms that depend on the time since the last reading need a refresh
+    pub fn on_tick(&mut self) {
+        self.alarms = evaluate_alarms(&self.alarm_rules, &self.devices, &self.overview);
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pending > 0
+    }
+
+    // Whether new rows of a device arrived recently enough to flash its tab
+    pub fn is_updated(&self, fan_index: usize) -> bool {
+        match self.updated_at.get(fan_index) {
+            Some(Some(updated_at)) => updated_at.elapsed() < UPDATE_FLASH_DURATION,
+            _ => false,
+        }
+    }
+
+    fn send(&mut self, request: Request) {
+        if self.worker.send(request) {
+            self.pending += 1;
+        } else {
+            self.error = Some(String::from("Database worker stopped"));
+        }
+    }
+
+    // Applies every result the worker has finished since the last call
+    pub fn receive(&mut self) {
+        while let Some(response) = self.worker.try_recv() {
+            if response.is_reply() {
+                self.pending = self.pending.saturating_sub(1);
+            }
+            self.handle_response(response);
+        }
+    }
+
+    fn handle_response(&mut self, response: Response) {
+        match response {
+            Response::Overview { overview, error } => {
+                self.error = error;
+                self.updated_at = vec![None; overview.len()];
+                self.overview = overview;
+                self.alarms = evaluate_alarms(&self.alarm_rules, &self.devices, &self.overview);
+            }
+            // Results for a device that is no longer selected are dropped
+            Response::FanData { table, .. }
+            | Response::History { table, .. }
+            | Response::Maintenance { table, .. }
+                if self.get_selected_table() != Some(table.as_str()) => {}
+            Response::FanData { result, .. } => match result {
+                Ok(fan_data) => self.fan_data = fan_data,
+                Err(error) => {
+                    self.error = Some(error.to_string());
+                    self.fan_data = None;
+                }
+            },
+            Response::History { result, .. } => {
+                self.history = match result {
+                    Ok(history) => history,
+                    Err(error) => {
+                        self.error = Some(error.to_string());
+                        Vec::new()
+                    }
+                };
+                self.frost_events = detect_frost_events(&self.history, self.frost_threshold);
+                self.wind_rose = get_wind_rose(&self.history);
+            }
+            Response::Maintenance { result, .. } => {
+                self.maintenance = match result {
+                    Ok(report) => Some(report),
+                    Err(error) => {
+                        self.error = Some(error.to_string());
+                        None
+                    }
+                };
+            }
+            Response::Changed { updates } => {
+                let now = Instant::now();
+                for (table, result) in updates {
+                    let fan_data = match result {
+                        Ok(fan_data) => fan_data,
+                        Err(error) => {
+                            self.error = Some(error.to_string());
+                            continue;
+                        }
+                    };
+                    let entries = self.overview.iter_mut().zip(self.updated_at.iter_mut());
+                    for (device, (entry, updated_at)) in self.devices.iter().zip(entries) {
+                        if device.table.as_deref() == Some(table.as_str()) {
+                            *entry = fan_data.clone();
+                            *updated_at = Some(now);
+                        }
+                    }
+                    if !self.is_overview_shown()
+                        && self.get_selected_table() == Some(table.as_str())
+                    {
+                        self.update_fan_data();
+                    }
+                }
+                self.alarms = evaluate_alarms(&self.alarm_rules, &self.devices, &self.overview);
+            }
+            Response::Export(result) => {
+                self.message = Some(match result {
+                    Ok((path, rows)) => format!("Exported {} rows to {}", rows, path.display()),
+                    Err(error) => format!("Export failed: {error}"),
+                });
+            }
+        }
+    }
+
     pub fn next(&mut self) {
+        if self.devices.is_empty() {
+            return;
+        }
         // Limit to 1 tab change per second
         let now = chrono::offset::Utc::now();
         if now.timestamp_millis() - 500 > self.last_change.timestamp_millis() {
-            self.index = (self.index + 1) % self.fans.len();
+            self.index = (self.index + 1) % self.devices.len();
             self.on_change();
         }
     }
 
     pub fn previous(&mut self) {
+        if self.devices.is_empty() {
+            return;
+        }
         // Limit to 1 tab change per second
         let now = chrono::offset::Utc::now();
         if now.timestamp_millis() - 500 > self.last_change.timestamp_millis() {
             if self.index > 0 {
                 self.index -= 1;
             } else {
-                self.index = self.fans.len() - 1;
+                self.index = self.devices.len() - 1;
             }
             self.on_change();
         }
     }
 
-    pub fn update_fan_data(&mut self) {
-        self.fan_data = match self.database.get_last_fan_data(self.index + 1) {
-            Ok(data) => data,
-            Err(error) => {
-                println!("{error:?}");
-                None
+    // Warning about data tables guessed without a mapping, for the selected
+    // device in its own views and as a count in the overviews
+    pub fn get_table_warning(&self) -> Option<String> {
+        if !self.is_overview_shown() {
+            return self.devices.get(self.index)?.get_table_warning();
+        }
+        let inferred = self
+            .devices
+            .iter()
+            .filter(|device| device.get_table_warning().is_some())
+            .count();
+        match inferred {
+            0 => None,
+            inferred => Some(format!(
+                "Tables of {inferred} devices are inferred from their order, add them to fan_tables to be sure"
+            )),
+        }
+    }
+
+    // Views listing the latest reading of several devices
+    pub fn is_overview_shown(&self) -> bool {
+        matches!(self.view, View::Overview | View::Groups | View::Watchdog)
+    }
+
+    // Devices in the overview table, only the selected group's in the groups
+    // view and in watchdog order in the watchdog view
+    pub fn get_rows(&self) -> Vec<usize> {
+        match (self.view, self.groups.get(self.group_index)) {
+            (View::Groups, Some(group)) => group.devices.clone(),
+            (View::Watchdog, _) => self.get_watchdog_rows(),
+            _ => (0..self.devices.len()).collect(),
+        }
+    }
+
+    // Every device, the longest silent first. Devices without a reading come
+    // before all others, ties keep the tab order.
+    fn get_watchdog_rows(&self) -> Vec<usize> {
+        let mut rows: Vec<usize> = (0..self.devices.len()).collect();
+        rows.sort_by_key(|index| Reverse(self.get_seconds_since_last(*index)));
+        rows
+    }
+
+    // Seconds since a device's latest reading, the most possible when it has none
+    pub fn get_seconds_since_last(&self, fan_index: usize) -> i64 {
+        match self.overview.get(fan_index) {
+            Some(Some(fan_data)) => fan_data.get_seconds_since_last().unwrap_or(i64::MAX),
+            _ => i64::MAX,
+        }
+    }
+
+    // Moves the overview selection without the tab change limit
+    pub fn next_row(&mut self) {
+        let rows = self.get_rows();
+        self.index = match rows.iter().position(|index| *index == self.index) {
+            Some(row) => rows[(row + 1) % rows.len()],
+            None => rows.first().copied().unwrap_or(self.index),
+        };
+    }
+
+    pub fn previous_row(&mut self) {
+        let rows = self.get_rows();
+        self.index = match rows.iter().position(|index| *index == self.index) {
+            Some(row) => rows[(row + rows.len() - 1) % rows.len()],
+            None => rows.last().copied().unwrap_or(self.index),
+        };
+    }
+
+    pub fn scroll_down(&mut self) {
+        if self.detail_scroll < self.detail_overflow.get() {
+            self.detail_scroll += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.detail_scroll = self.detail_scroll.saturating_sub(1);
+    }
+
+    pub fn open_detail(&mut self) {
+        self.view = View::Detail;
+        self.on_change();
+    }
+
+    pub fn open_overview(&mut self) {
+        self.view = View::Overview;
+        self.update_overview();
+    }
+
+    // Opens the group of the selected device, or the first group when it has none
+    pub fn open_groups(&mut self) {
+        if self.groups.is_empty() {
+            self.message = Some(String::from(
+                "No groups, see the fan_groups table or [groups] in the config file",
+            ));
+            return;
+        }
+        match self
+            .groups
+            .iter()
+            .position(|group| group.devices.contains(&self.index))
+        {
+            Some(group_index) => self.group_index = group_index,
+            None => self.select_group(self.group_index.min(self.groups.len() - 1)),
+        }
+        self.view = View::Groups;
+        self.update_overview();
+    }
+
+    pub fn toggle_groups(&mut self) {
+        match self.view {
+            View::Groups => self.open_overview(),
+            View::Overview | View::Watchdog | View::Detail | View::History | View::Maintenance => {
+                self.open_groups()
+            }
+        }
+    }
+
+    pub fn toggle_watchdog(&mut self) {
+        match self.view {
+            View::Watchdog => self.open_overview(),
+            View::Overview | View::Groups | View::Detail | View::History | View::Maintenance => {
+                self.view = View::Watchdog;
+                self.update_overview();
+            }
+        }
+    }
+
+    // Selects a group and its first device
+    pub fn select_group(&mut self, group_index: usize) {
+        if let Some(group) = self.groups.get(group_index) {
+            self.group_index = group_index;
+            self.index = group.devices[0];
+        }
+    }
+
+    pub fn next_group(&mut self) {
+        if !self.groups.is_empty() {
+            self.select_group((self.group_index + 1) % self.groups.len());
+        }
+    }
+
+    pub fn previous_group(&mut self) {
+        if !self.groups.is_empty() {
+            self.select_group((self.group_index + self.groups.len() - 1) % self.groups.len());
+        }
+    }
+
+    pub fn toggle_overview(&mut self) {
+        match self.view {
+            View::Overview | View::Groups | View::Watchdog => self.open_detail(),
+            View::Detail | View::History | View::Maintenance => self.open_overview(),
+        }
+    }
+
+    pub fn toggle_help(&mut self) {
+        self.show_help = !self.show_help;
+    }
+
+    pub fn toggle_history(&mut self) {
+        self.history_field = None;
+        self.view = match self.view {
+            View::History => View::Detail,
+            View::Overview | View::Groups | View::Watchdog | View::Detail | View::Maintenance => {
+                View::History
             }
         };
+        self.update_fan_data();
+    }
+
+    pub fn toggle_maintenance(&mut self) {
+        if !self.is_fan_selected() && self.view != View::Maintenance {
+            self.message = Some(String::from("Maintenance is only available for fans"));
+            return;
+        }
+        self.view = match self.view {
+            View::Maintenance => View::Detail,
+            View::Overview | View::Groups | View::Watchdog | View::Detail | View::History => {
+                View::Maintenance
+            }
+        };
+        self.update_fan_data();
+    }
+
+    pub fn toggle_wind_rose(&mut self) {
+        self.show_wind_rose = !self.show_wind_rose;
+    }
+
+    pub fn next_history_window(&mut self) {
+        self.history_window = self.history_window.next();
+        self.update_history();
+    }
+
+    pub fn zoom_history_window(&mut self, shorter: bool) {
+        let history_window = match shorter {
+            true => self.history_window.shorter(),
+            false => self.history_window.longer(),
+        };
+        if history_window != self.history_window {
+            self.history_window = history_window;
+            self.update_history();
+        }
+    }
+
+    // Selects a device without the tab change limit, opening it from the overview
+    pub fn select(&mut self, index: usize) {
+        if index >= self.devices.len() {
+            return;
+        }
+        let changed = index != self.index;
+        self.index = index;
+        if self.is_overview_shown() {
+            self.open_detail();
+        } else if changed {
+            self.on_change();
+        }
+    }
+
+    pub fn open_field_history(&mut self, field: &'static FieldInfo) {
+        self.history_field = Some(field);
+        self.view = View::History;
+        self.update_fan_data();
+    }
+
+    pub fn open_search(&mut self) {
+        self.search = Some(Search::new(&self.devices, self.index));
+    }
+
+    pub fn close_search(&mut self) {
+        self.search = None;
+    }
+
+    pub fn search_input(&mut self, character: char) {
+        if let Some(search) = &mut self.search {
+            let query = format!("{}{character}", search.query);
+            search.set_query(&self.devices, query);
+        }
+    }
+
+    pub fn search_backspace(&mut self) {
+        if let Some(search) = &mut self.search {
+            let mut query = search.query.clone();
+            query.pop();
+            search.set_query(&self.devices, query);
+        }
+    }
+
+    pub fn move_search_selection(&mut self, down: bool) {
+        if let Some(search) = &mut self.search {
+            search.move_selection(down);
+        }
+    }
+
+    // Jumps to the highlighted match
+    pub fn confirm_search(&mut self) {
+        if let Some(index) = self
+            .search
+            .take()
+            .and_then(|search| search.get_selected_device())
+        {
+            self.select(index);
+        }
+    }
+
+    // Topmost widget at a terminal cell
+    fn get_mouse_target(&self, column: u16, row: u16) -> Option<MouseTarget> {
+        self.mouse_targets
+            .borrow()
+            .iter()
+            .rev()
+            .find(|(area, _)| {
+                column >= area.left()
+                    && column < area.right()
+                    && row >= area.top()
+                    && row < area.bottom()
+            })
+            .map(|(_, target)| *target)
+    }
+
+    // Clicks only close the help popup while it covers the view
+    pub fn on_click(&mut self, column: u16, row: u16) {
+        if self.show_help {
+            self.show_help = false;
+            return;
+        }
+        match self.get_mouse_target(column, row) {
+            Some(MouseTarget::Tab(index)) => {
+                self.close_search();
+                self.select(index);
+            }
+            Some(MouseTarget::GroupTab(group_index)) => self.select_group(group_index),
+            Some(MouseTarget::Metric(field)) => self.open_field_history(field),
+            _ => {}
+        }
+    }
+
+    // Scrolling over a chart zooms its time window, over the detail view
+    // scrolls panels that don't fit
+    pub fn on_scroll(&mut self, column: u16, row: u16, down: bool) {
+        if self.show_help {
+            return;
+        }
+        match (self.get_mouse_target(column, row), down) {
+            (Some(MouseTarget::Chart), down) => self.zoom_history_window(!down),
+            (Some(MouseTarget::Detail | MouseTarget::Metric(_)), true) => self.scroll_down(),
+            (Some(MouseTarget::Detail | MouseTarget::Metric(_)), false) => self.scroll_up(),
+            _ => {}
+        }
+    }
+
+    // Run hours are only counted for fans
+    fn is_fan_selected(&self) -> bool {
+        match self.devices.get(self.index) {
+            Some(device) => device.device_type.layout == Layout::Fan,
+            None => false,
+        }
+    }
+
+    // FanN table of the selected device
+    fn get_selected_table(&self) -> Option<&str> {
+        self.devices.get(self.index)?.table.as_deref()
+    }
+
+    fn get_selected_device_type(&self) -> Option<&'static DeviceType> {
+        Some(self.devices.get(self.index)?.device_type)
+    }
+
+    pub fn update_fan_data(&mut self) {
+        let (table, device_type) =
+            match (self.get_selected_table(), self.get_selected_device_type()) {
+                (Some(table), Some(device_type)) => (table.to_string(), device_type),
+                _ => {
+                    self.fan_data = None;
+                    self.history = Vec::new();
+                    self.frost_events = Vec::new();
+                    self.wind_rose = WindRose::default();
+                    self.maintenance = None;
+                    return;
+                }
+            };
+        self.send(Request::FanData { table, device_type });
+        // The detail view needs the history for frost events
+        if !self.is_overview_shown() {
+            self.update_history();
+        }
+        if self.view == View::Maintenance {
+            self.update_maintenance();
+        }
+    }
+
+    pub fn update_maintenance(&mut self) {
+        if let Some(table) = self.get_selected_table() {
+            let table = table.to_string();
+            self.send(Request::Maintenance {
+                table,
+                days: MAINTENANCE_DAYS,
+                season_start: self.config.season_start,
+            });
+        }
+    }
+
+    pub fn update_overview(&mut self) {
+        let devices = self.devices.clone();
+        self.send(Request::Overview { devices });
+    }
+
+    // Highest severity of the active alarms of a device
+    pub fn get_alarm_severity(&self, fan_index: usize) -> Option<AlarmSeverity> {
+        self.alarms
+            .iter()
+            .filter(|alarm| alarm.fan_index == fan_index)
+            .map(|alarm| alarm.severity)
+            .max()
+    }
+
+    // Staleness policy of a device, the default one when there is no such device
+    pub fn get_staleness_policy(&self, fan_index: usize) -> StalenessPolicy {
+        self.devices
+            .get(fan_index)
+            .map(|device| device.staleness)
+            .unwrap_or_default()
+    }
+
+    // Worst of a device's reading status and its active alarms
+    pub fn get_device_severity(&self, fan_index: usize) -> Severity {
+        let fan_data = self.overview.get(fan_index).and_then(Option::as_ref);
+        let status = get_device_severity(fan_data, &self.get_staleness_policy(fan_index));
+        match self.get_alarm_severity(fan_index) {
+            Some(alarm) => status.max(Severity::from(alarm)),
+            None => status,
+        }
+    }
+
+    pub fn update_history(&mut self) {
+        if let (Some(table), Some(device_type)) =
+            (self.get_selected_table(), self.get_selected_device_type())
+        {
+            let table = table.to_string();
+            let since = self.get_history_start();
+            self.send(Request::History {
+                table,
+                device_type,
+                since,
+            });
+        }
+    }
+
+    // Start of the history window in the FanN datetime format
+    fn get_history_start(&self) -> String {
+        let since = chrono::offset::Local::now().naive_local() - self.history_window.duration();
+        since.format(DATETIME_FORMAT).to_string()
+    }
+
+    // Overview exports every device's latest reading, the groups view those of
+    // the selected group and the watchdog view all of them in its order, other
+    // views the selected device's history over the current history window
+    pub fn export(&mut self, format: ExportFormat) {
+        match self.view {
+            View::Overview | View::Groups | View::Watchdog => {
+                let rows = self.get_rows();
+                let devices: Vec<Device> = rows
+                    .iter()
+                    .map(|index| self.devices[*index].clone())
+                    .collect();
+                let overview: Vec<Option<FanData>> = rows
+                    .iter()
+                    .map(|index| self.overview.get(*index).cloned().flatten())
+                    .collect();
+                let name = match (self.view, self.groups.get(self.group_index)) {
+                    (View::Groups, Some(group)) => group.name.as_str(),
+                    (View::Watchdog, _) => "watchdog",
+                    _ => "all",
+                };
+                let path = get_default_export_path(name, format);
+                let result = export_snapshot(&path, &devices, &overview, format);
+                self.handle_response(Response::Export(
+                    result
+                        .map(|rows| (path, rows))
+                        .map_err(|error| error.to_string()),
+                ));
+            }
+            View::Detail | View::History | View::Maintenance => {
+                match self.devices.get(self.index) {
+                    Some(device) => {
+                        let request = Request::ExportHistory {
+                            device: device.clone(),
+                            since: self.get_history_start(),
+                            path: get_default_export_path(device.get_name(), format),
+                            format,
+                        };
+                        self.send(request);
+                    }
+                    None => self.message = Some(String::from("Export failed: No device selected")),
+                }
+            }
+        }
     }
 
     // #[cfg(target_os = "linux")]
@@ -0,0 +1,489 @@
+use crate::config::Config;
+use crate::device::Layout;
+use crate::export::{
+    escape_csv, export_history, export_snapshot, get_default_export_path, ExportFormat,
+};
+use crate::fan_data::{FanData, DATETIME_FORMAT, FIELDS};
+use crate::maintenance::{get_maintenance_report, MaintenanceReport, RunningHistory};
+use crate::sqlite::{Database, Device};
+use crate::staleness::StalenessPolicy;
+use clap::{Subcommand, ValueEnum};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List all devices
+    List {
+        #[clap(short, long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// Print the latest readings of all devices or a single device
+    Status {
+        /// Device name, serial number or MAC address
+        #[clap(long, alias = "fan")]
+        device: Option<String>,
+        #[clap(short, long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// Print every reading of a device since a point in time
+    History {
+        /// Device name, serial number or MAC address
+        #[clap(long, alias = "fan")]
+        device: String,
+        /// How far back to go, e.g. 30m, 6h, 7d
+        #[clap(long, default_value = "6h")]
+        since: String,
+        #[clap(short, long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// Print run hours, starts and the longest run of all fans or a single fan
+    Maintenance {
+        /// Fan name, serial number or MAC address
+        #[clap(long)]
+        fan: Option<String>,
+        /// Number of days listed individually
+        #[clap(long, default_value_t = 7)]
+        days: usize,
+        #[clap(short, long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+    /// Write the latest readings of every device, or the history of one device, to a file
+    Export {
+        /// Export this device's history instead of every device's latest reading
+        #[clap(long, alias = "fan")]
+        device: Option<String>,
+        /// How far back the history goes, e.g. 30m, 6h, 7d
+        #[clap(long, default_value = "24h")]
+        since: String,
+        /// Output file, defaults to a timestamped file in the working directory
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        #[clap(short, long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Serialize)]
+struct DeviceStatus<'a> {
+    device: &'a Device,
+    data: Option<FanData>,
+}
+
+#[derive(Serialize)]
+struct FanMaintenance<'a> {
+    fan: &'a Device,
+    report: MaintenanceReport,
+}
+
+// Column header and value of one printed reading
+type ReadingColumn = (&'static str, fn(&FanData) -> String);
+
+// Columns that describe the reading relative to now and the device's
+// staleness policy, only used for status
+type LiveColumn = (&'static str, fn(&FanData, &StalenessPolicy) -> String);
+
+const LIVE_COLUMNS: [LiveColumn; 2] = [
+    ("connection_status", |d, policy| {
+        d.get_connection_status_string(policy)
+    }),
+    ("running_status", |d, policy| {
+        d.get_running_status_string(policy)
+    }),
+];
+
+// Groups and staleness policies come from the config file like in the UI
+pub fn run(database: &Database, command: Command, config: &Config) -> Result<(), String> {
+    let mut devices = database.get_devices().map_err(|error| error.to_string())?;
+    config.apply_to_devices(&mut devices);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = match command {
+        Command::List { format } => write_devices(&mut out, &devices, format),
+        Command::Status { device, format } => {
+            let selected = match device {
+                Some(query) => vec![find_device(&devices, &query)?],
+                None => devices.iter().collect(),
+            };
+            let mut statuses = Vec::new();
+            for device in selected {
+                let data = get_latest(database, device)?;
+                statuses.push(DeviceStatus { device, data });
+            }
+            write_statuses(&mut out, &statuses, format)
+        }
+        Command::History {
+            device,
+            since,
+            format,
+        } => {
+            let device = find_device(&devices, &device)?;
+            let history = get_history(database, device, &since)?;
+            write_history(&mut out, &history, format)
+        }
+        Command::Maintenance { fan, days, format } => {
+            let selected = match fan {
+                Some(query) => {
+                    let device = find_device(&devices, &query)?;
+                    if device.device_type.layout != Layout::Fan {
+                        return Err(format!(
+                            "{} is a {}, run hours are only counted for fans",
+                            device.name, device.device_type.name
+                        ));
+                    }
+                    vec![device]
+                }
+                None => devices
+                    .iter()
+                    .filter(|device| device.device_type.layout == Layout::Fan)
+                    .collect(),
+            };
+            let now = chrono::offset::Local::now().naive_local();
+            let mut reports = Vec::new();
+            for fan in selected {
+                let mut history = RunningHistory::default();
+                if let Some(table) = &fan.table {
+                    database
+                        .update_running_history(table, &mut history)
+                        .map_err(|error| error.to_string())?;
+                }
+                let report =
+                    get_maintenance_report(&history.samples, now, days, config.season_start);
+                reports.push(FanMaintenance { fan, report });
+            }
+            write_maintenance(&mut out, &reports, format)
+        }
+        Command::Export {
+            device,
+            since,
+            output,
+            format,
+        } => {
+            let (path, rows) = match device {
+                Some(query) => {
+                    let device = find_device(&devices, &query)?;
+                    let history = get_history(database, device, &since)?;
+                    let path = output
+                        .unwrap_or_else(|| get_default_export_path(device.get_name(), format));
+                    let rows = export_history(&path, device, &history, format)
+                        .map_err(|error| error.to_string())?;
+                    (path, rows)
+                }
+                None => {
+                    let mut overview = Vec::new();
+                    for device in &devices {
+                        overview.push(get_latest(database, device)?);
+                    }
+                    let path = output.unwrap_or_else(|| get_default_export_path("all", format));
+                    let rows = export_snapshot(&path, &devices, &overview, format)
+                        .map_err(|error| error.to_string())?;
+                    (path, rows)
+                }
+            };
+            writeln!(out, "Exported {} rows to {}", rows, path.display())
+        }
+    };
+    match result {
+        // Output piped into e.g. head was closed early
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        Err(error) => Err(error.to_string()),
+        Ok(()) => Ok(()),
+    }
+}
+
+fn get_latest(database: &Database, device: &Device) -> Result<Option<FanData>, String> {
+    match &device.table {
+        Some(table) => database
+            .get_last_fan_data(table, device.device_type)
+            .map_err(|error| error.to_string()),
+        None => Ok(None),
+    }
+}
+
+fn get_history(database: &Database, device: &Device, since: &str) -> Result<Vec<FanData>, String> {
+    let table = device
+        .table
+        .as_ref()
+        .ok_or_else(|| device.get_table_error())?;
+    let since = get_since(chrono::offset::Local::now().naive_local(), since)?;
+    database
+        .get_fan_data_history(
+            table,
+            device.device_type,
+            &since.format(DATETIME_FORMAT).to_string(),
+        )
+        .map_err(|error| error.to_string())
+}
+
+// Start of a --since window, durations reaching back before the earliest date
+// chrono can hold are rejected rather than panicking
+fn get_since(now: chrono::NaiveDateTime, since: &str) -> Result<chrono::NaiveDateTime, String> {
+    now.checked_sub_signed(parse_duration(since)?)
+        .ok_or_else(|| String::from("--since is too far back"))
+}
+
+// Matches a device by name, serial number or MAC address, ignoring case
+pub fn find_device<'a>(devices: &'a [Device], query: &str) -> Result<&'a Device, String> {
+    devices
+        .iter()
+        .find(|device| {
+            device.name.eq_ignore_ascii_case(query)
+                || device.serial_number.eq_ignore_ascii_case(query)
+                || device
+                    .mac
+                    .as_deref()
+                    .is_some_and(|mac| mac.eq_ignore_ascii_case(query))
+        })
+        .ok_or_else(|| format!("No device matching \"{query}\""))
+}
+
+// Parses positive durations such as 90s, 30m, 6h, 7d or 2w
+pub fn parse_duration(value: &str) -> Result<chrono::Duration, String> {
+    let value = value.trim();
+    let error = || format!("Invalid duration \"{value}\", expected e.g. 90s, 30m, 6h or 7d");
+    let (position, _) = value.char_indices().last().ok_or_else(error)?;
+    let (number, unit) = value.split_at(position);
+    let number: i64 = number.parse().map_err(|_| error())?;
+    if number <= 0 {
+        return Err(error());
+    }
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return Err(error()),
+    };
+    // Durations chrono can't hold are rejected rather than panicking
+    number
+        .checked_mul(unit_seconds)
+        .filter(|seconds| *seconds <= chrono::Duration::max_value().num_seconds())
+        .map(chrono::Duration::seconds)
+        .ok_or_else(error)
+}
+
+pub fn write_devices(out: &mut dyn Write, devices: &[Device], format: Format) -> io::Result<()> {
+    if format == Format::Json {
+        return write_json(out, &devices);
+    }
+    let headers = [
+        "name",
+        "type",
+        "serial_number",
+        "mac",
+        "table",
+        "table_source",
+        "group",
+    ];
+    let rows = devices
+        .iter()
+        .map(|device| {
+            vec![
+                device.name.clone(),
+                device.device_type.name.to_string(),
+                device.serial_number.clone(),
+                device.mac.clone().unwrap_or_default(),
+                device.table.clone().unwrap_or_default(),
+                device
+                    .table_source
+                    .map(|source| source.label().to_string())
+                    .unwrap_or_default(),
+                device.group.clone().unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    write_rows(out, &headers, &rows, format)
+}
+
+fn write_statuses(
+    out: &mut dyn Write,
+    statuses: &[DeviceStatus],
+    format: Format,
+) -> io::Result<()> {
+    if format == Format::Json {
+        return write_json(out, &statuses);
+    }
+    let columns: Vec<ReadingColumn> = FIELDS
+        .iter()
+        .map(|field| (field.key, field.display))
+        .collect();
+    let mut headers = vec!["name"];
+    headers.extend(LIVE_COLUMNS.iter().map(|(header, _)| *header));
+    headers.extend(columns.iter().map(|(header, _)| *header));
+    let mut rows = Vec::new();
+    for status in statuses {
+        let mut row = vec![status.device.name.clone()];
+        match &status.data {
+            Some(data) => {
+                let policy = &status.device.staleness;
+                row.extend(LIVE_COLUMNS.iter().map(|(_, value)| value(data, policy)));
+                row.extend(columns.iter().map(|(_, value)| value(data)));
+            }
+            None => row.extend(headers[1..].iter().map(|_| String::from("No Data"))),
+        }
+        rows.push(row);
+    }
+    write_rows(out, &headers, &rows, format)
+}
+
+fn write_history(out: &mut dyn Write, history: &[FanData], format: Format) -> io::Result<()> {
+    if format == Format::Json {
+        return write_json(out, &history);
+    }
+    let headers: Vec<&str> = FIELDS.iter().map(|field| field.key).collect();
+    let rows = history
+        .iter()
+        .map(|data| FIELDS.iter().map(|field| (field.display)(data)).collect())
+        .collect::<Vec<Vec<String>>>();
+    write_rows(out, &headers, &rows, format)
+}
+
+fn write_maintenance(
+    out: &mut dyn Write,
+    reports: &[FanMaintenance],
+    format: Format,
+) -> io::Result<()> {
+    if format == Format::Json {
+        return write_json(out, &reports);
+    }
+    let headers = ["name", "period", "run_hours", "starts", "longest_run_hours"];
+    let mut rows = Vec::new();
+    for FanMaintenance { fan, report } in reports {
+        let mut periods = vec![
+            ("today", &report.today),
+            ("season", &report.season),
+            ("lifetime", &report.lifetime),
+        ];
+        periods.extend(
+            report
+                .days
+                .iter()
+                .map(|day| (day.date.as_str(), &day.stats)),
+        );
+        for (period, stats) in periods {
+            rows.push(vec![
+                fan.name.clone(),
+                period.to_string(),
+                format!("{:.1}", stats.run_seconds as f64 / 3600.0),
+                stats.starts.to_string(),
+                format!("{:.1}", stats.longest_run_seconds as f64 / 3600.0),
+            ]);
+        }
+    }
+    write_rows(out, &headers, &rows, format)
+}
+
+fn write_json<T: Serialize>(out: &mut dyn Write, value: &T) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)
+}
+
+fn write_rows(
+    out: &mut dyn Write,
+    headers: &[&str],
+    rows: &[Vec<String>],
+    format: Format,
+) -> io::Result<()> {
+    match format {
+        Format::Csv => {
+            writeln!(out, "{}", headers.join(","))?;
+            for row in rows {
+                let row: Vec<String> = row.iter().map(|value| escape_csv(value)).collect();
+                writeln!(out, "{}", row.join(","))?;
+            }
+        }
+        Format::Table | Format::Json => {
+            // Pad every column to its widest value
+            let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+            for row in rows {
+                for (width, value) in widths.iter_mut().zip(row) {
+                    *width = (*width).max(value.chars().count());
+                }
+            }
+            let format_row = |values: Vec<&str>| {
+                values
+                    .iter()
+                    .zip(&widths)
+                    .map(|(value, width)| format!("{:<width$}", value, width = width))
+                    .collect::<Vec<String>>()
+                    .join("  ")
+            };
+            writeln!(out, "{}", format_row(headers.to_vec()).trim_end())?;
+            for row in rows {
+                let values = row.iter().map(|v| v.as_str()).collect();
+                writeln!(out, "{}", format_row(values).trim_end())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_unit() {
+        assert_eq!(parse_duration("90s"), Ok(chrono::Duration::seconds(90)));
+        assert_eq!(parse_duration("30m"), Ok(chrono::Duration::minutes(30)));
+        assert_eq!(parse_duration(" 6h "), Ok(chrono::Duration::hours(6)));
+        assert_eq!(parse_duration("7d"), Ok(chrono::Duration::days(7)));
+        assert_eq!(parse_duration("2w"), Ok(chrono::Duration::weeks(2)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for value in ["", "h", "6", "6x", "six h", "6 h", "6hh"] {
+            assert!(parse_duration(value).is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_zero_and_negative_durations() {
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("-6h").is_err());
+    }
+
+    #[test]
+    fn rejects_non_ascii_units_without_panicking() {
+        assert_eq!(
+            parse_duration("5é").unwrap_err(),
+            "Invalid duration \"5é\", expected e.g. 90s, 30m, 6h or 7d"
+        );
+        assert!(parse_duration("5分").is_err());
+        assert!(parse_duration("é").is_err());
+    }
+
+    #[test]
+    fn rejects_durations_too_long_to_hold() {
+        assert!(parse_duration("9223372036854775807w").is_err());
+        assert!(parse_duration("9223372036854775807s").is_err());
+    }
+    #[test]
+    fn since_counts_back_from_now() {
+        let now = chrono::NaiveDate::from_ymd(2024, 5, 1).and_hms(12, 0, 0);
+        assert_eq!(
+            get_since(now, "6h"),
+            Ok(chrono::NaiveDate::from_ymd(2024, 5, 1).and_hms(6, 0, 0))
+        );
+        assert!(get_since(now, "6x").is_err());
+    }
+
+    #[test]
+    fn rejects_since_before_the_earliest_date() {
+        let now = chrono::NaiveDate::from_ymd(2024, 5, 1).and_hms(12, 0, 0);
+        assert_eq!(
+            get_since(now, "100000000w"),
+            Err(String::from("--since is too far back"))
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use crate::config::Theme;
+use crate::wind::{WindRose, CARDINAL_DIRECTIONS};
+use tui::buffer::Buffer;
+use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use tui::symbols::Marker;
+use tui::widgets::canvas::{Canvas, Context, Line, Points};
+use tui::widgets::{Block, Paragraph, Widget};
+
+// Points used to draw the compass ring
+const RING_POINTS: usize = 72;
+
+// Rows the compass needs before the cardinal points are labelled
+const MIN_LABELLED_HEIGHT: u16 = 7;
+
+// Terminal cells are about twice as tall as they are wide
+const CELL_ASPECT: f64 = 2.0;
+
+// Compass showing the direction the wind comes from, or a wind rose when one
+// is given, with a one line label underneath
+pub struct Compass<'a> {
+    block: Option<Block<'a>>,
+    direction: Option<i16>,
+    rose: Option<&'a WindRose>,
+    label: String,
+    show_labels: bool,
+    theme: Theme,
+}
+
+impl<'a> Compass<'a> {
+    pub fn new(direction: Option<i16>, label: String) -> Compass<'a> {
+        Compass {
+            block: None,
+            direction,
+            rose: None,
+            label,
+            show_labels: true,
+            theme: Theme::default(),
+        }
+    }
+
+    pub fn block(mut self, block: Block<'a>) -> Compass<'a> {
+        self.block = Some(block);
+        self
+    }
+
+    pub fn rose(mut self, rose: &'a WindRose) -> Compass<'a> {
+        self.rose = Some(rose);
+        self
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Compass<'a> {
+        self.theme = theme;
+        self
+    }
+
+    fn paint(&self, ctx: &mut Context) {
+        let ring: Vec<(f64, f64)> = (0..RING_POINTS)
+            .map(|point| get_point(point as f64 * 360.0 / RING_POINTS as f64, 1.0))
+            .collect();
+        ctx.draw(&Points {
+            coords: &ring,
+            color: self.theme.axis,
+        });
+        // Labels would cover most of the ring on small compasses
+        if self.show_labels {
+            for (index, label) in CARDINAL_DIRECTIONS.iter().enumerate().step_by(2) {
+                let (x, y) = get_point(index as f64 * 45.0, 1.0);
+                ctx.print(x, y, *label);
+            }
+        }
+        ctx.layer();
+        match (self.rose, self.direction) {
+            (Some(rose), _) => paint_rose(ctx, rose, &self.theme),
+            (None, Some(direction)) => {
+                let (x, y) = get_point(direction as f64, 0.8);
+                ctx.draw(&Line {
+                    x1: 0.0,
+                    y1: 0.0,
+                    x2: x,
+                    y2: y,
+                    color: self.theme.needle,
+                });
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+impl<'a> Widget for Compass<'a> {
+    fn render(mut self, area: Rect, buf: &mut Buffer) {
+        let area = match self.block.take() {
+            Some(block) => {
+                let inner = block.inner(area);
+                block.render(area, buf);
+                inner
+            }
+            None => area,
+        };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(1)].as_ref())
+            .split(area);
+        let label = Paragraph::new(self.label.as_str()).alignment(Alignment::Center);
+        label.render(chunks[1], buf);
+
+        // Too small to draw a readable compass, the label still shows the heading
+        let canvas_area = chunks[0];
+        if canvas_area.height < 3 || canvas_area.width < 6 {
+            return;
+        }
+        self.show_labels = canvas_area.height >= MIN_LABELLED_HEIGHT;
+        // Stretch the bounds along the longer side so the ring stays round
+        let width = canvas_area.width as f64;
+        let height = canvas_area.height as f64 * CELL_ASPECT;
+        let x_bound = 1.2 * (width / height).max(1.0);
+        let y_bound = 1.2 * (height / width).max(1.0);
+        Canvas::default()
+            .marker(Marker::Braille)
+            .background_color(self.theme.background)
+            .x_bounds([-x_bound, x_bound])
+            .y_bounds([-y_bound, y_bound])
+            .paint(|ctx| self.paint(ctx))
+            .render(canvas_area, buf);
+    }
+}
+
+// Petal per sector, as long as the sector's share of the most frequent one
+// and colored by its mean speed, calm to strong in ok to critical colors
+fn paint_rose(ctx: &mut Context, rose: &WindRose, theme: &Theme) {
+    let max_frequency = (0..rose.sectors.len())
+        .map(|sector| rose.get_frequency(sector))
+        .fold(0.0, f64::max);
+    if max_frequency <= 0.0 {
+        return;
+    }
+    for (index, sector) in rose.sectors.iter().enumerate() {
+        let length = 0.9 * rose.get_frequency(index) / max_frequency;
+        if length <= 0.0 {
+            continue;
+        }
+        let color = match sector.get_mean_speed() {
+            speed if speed < 2.0 => theme.ok,
+            speed if speed < 5.0 => theme.warning,
+            _ => theme.critical,
+        };
+        // A fan of lines gives each petal some width
+        for offset in [-10.0, -5.0, 0.0, 5.0, 10.0] {
+            let (x, y) = get_point(index as f64 * 45.0 + offset, length);
+            ctx.draw(&Line {
+                x1: 0.0,
+                y1: 0.0,
+                x2: x,
+                y2: y,
+                color,
+            });
+        }
+    }
+}
+
+// Canvas point of a compass heading, degrees clockwise from north
+fn get_point(degrees: f64, radius: f64) -> (f64, f64) {
+    let radians = degrees.to_radians();
+    (radius * radians.sin(), radius * radians.cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_text(buffer: &Buffer) -> String {
+        let area = buffer.area();
+        (0..area.height)
+            .map(|y| {
+                (0..area.width)
+                    .map(|x| buffer.get(x, y).symbol.as_str())
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn points_go_clockwise_from_north() {
+        let close = |(x, y): (f64, f64), (expected_x, expected_y): (f64, f64)| {
+            (x - expected_x).abs() < 1e-9 && (y - expected_y).abs() < 1e-9
+        };
+        assert!(close(get_point(0.0, 1.0), (0.0, 1.0)));
+        assert!(close(get_point(90.0, 1.0), (1.0, 0.0)));
+        assert!(close(get_point(180.0, 0.5), (0.0, -0.5)));
+        assert!(close(get_point(270.0, 1.0), (-1.0, 0.0)));
+        assert!(close(get_point(360.0, 1.0), get_point(0.0, 1.0)));
+    }
+
+    #[test]
+    fn large_compasses_label_the_cardinal_points() {
+        let area = Rect::new(0, 0, 30, 12);
+        let mut buffer = Buffer::empty(area);
+        Compass::new(Some(350), String::from("N 2.0 m/s")).render(area, &mut buffer);
+        let text = get_text(&buffer);
+        for label in ["N", "E", "S", "W"] {
+            assert!(text.contains(label), "{label} in\n{text}");
+        }
+        assert!(text.lines().last().unwrap().contains("N 2.0 m/s"));
+    }
+
+    #[test]
+    fn small_compasses_only_show_the_label() {
+        let area = Rect::new(0, 0, 12, 3);
+        let mut buffer = Buffer::empty(area);
+        Compass::new(None, String::from("Calm")).render(area, &mut buffer);
+        let text = get_text(&buffer);
+        assert_eq!(
+            text.lines().filter(|line| !line.trim().is_empty()).count(),
+            1
+        );
+        assert!(text.contains("Calm"));
+    }
+
+    #[test]
+    fn empty_roses_draw_no_petals() {
+        let rose = WindRose::default();
+        let area = Rect::new(0, 0, 30, 12);
+        let mut with_rose = Buffer::empty(area);
+        Compass::new(None, String::new())
+            .rose(&rose)
+            .render(area, &mut with_rose);
+        let mut without = Buffer::empty(area);
+        Compass::new(None, String::new()).render(area, &mut without);
+        assert_eq!(get_text(&with_rose), get_text(&without));
+    }
+}
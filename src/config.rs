@@ -0,0 +1,788 @@
+use crate::cli::parse_duration;
+use crate::device::Layout;
+use crate::fan_data::{FieldInfo, AIR_TEMPERATURE, FIELDS};
+use crate::group::apply_config_groups;
+use crate::keymap::{parse_keymap, Keymap};
+use crate::maintenance::SeasonStart;
+use crate::sqlite::Device;
+use crate::staleness::{PolicyOverride, StalenessPolicy, StalenessRules};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use tui::style::Color;
+
+// Panels and themes used for everything the config file leaves out. Panels
+// fill the detail view left to right, top to bottom.
+const DEFAULT_CONFIG: &str = r##"
+theme = "dark"
+
+[themes.light]
+background = "white"
+foreground = "black"
+highlight = "blue"
+border = "blue"
+selection = "gray"
+trend = "blue"
+series = ["red", "blue", "green", "#af5f00", "magenta"]
+axis = "darkgray"
+needle = "#af5f00"
+ok = "green"
+unknown = "darkgray"
+warning = "#af5f00"
+critical = "red"
+
+[[panels.fan]]
+title = "General Information"
+fields = ["battery_voltage", "connection_status", "last_update"]
+
+[[panels.fan]]
+title = "Temperature"
+fields = ["temperature_top", "temperature_bottom", "temperature_far"]
+
+[[panels.fan]]
+title = "Environmental Data"
+fields = ["humidity", "wind"]
+
+[[panels.fan]]
+title = "Fan Status"
+fields = ["vsd_ready", "vsd_running", "vsd_command"]
+
+[[panels.fan]]
+title = "Fan Data"
+fields = ["motor_current", "motor_vibration", "main_state"]
+
+[[panels.fan]]
+title = "Additional Data"
+fields = ["signal_strength", "rain_meter"]
+
+[[panels.fan]]
+title = "Panel Switch & Door Status"
+fields = ["main_switch", "main_panel_door", "control_door"]
+
+[[panels.fan]]
+title = "Main Panel Status"
+fields = ["control_method", "running_status"]
+
+[[panels.fan]]
+title = "Frost (Last {window})"
+fields = ["inversion", "far_difference", "frost_event"]
+
+[[panels.weather_station]]
+title = "General Information"
+fields = ["battery_voltage", "connection_status", "last_update"]
+
+[[panels.weather_station]]
+title = "Wind"
+fields = ["wind"]
+
+[[panels.weather_station]]
+title = "Weather"
+fields = ["air_temperature", "humidity", "rain_meter"]
+
+[[panels.weather_station]]
+title = "Additional Data"
+fields = ["signal_strength"]
+
+[[panels.weather_station]]
+title = "Wind Rose (Last {window})"
+fields = ["wind_rose"]
+
+[[panels.temperature_probe]]
+title = "General Information"
+fields = ["battery_voltage", "connection_status", "last_update"]
+
+[[panels.temperature_probe]]
+title = "Temperature"
+fields = ["temperature_top", "temperature_bottom", "temperature_far"]
+
+[[panels.temperature_probe]]
+title = "Additional Data"
+fields = ["signal_strength"]
+"##;
+
+// Chart line colors of a theme, shorter lists in the config file repeat
+pub const SERIES_COLORS: usize = 5;
+
+// Colors the UI is drawn with
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub background: Color,
+    pub foreground: Color,
+    // Selected tab and status messages
+    pub highlight: Color,
+    // Borders of values that don't need attention
+    pub border: Color,
+    // Selected overview row and devices with new readings
+    pub selection: Color,
+    // Sparklines
+    pub trend: Color,
+    // Lines of the history charts, the top chart uses the first three
+    pub series: [Color; SERIES_COLORS],
+    // Chart axes and the compass ring
+    pub axis: Color,
+    // Compass needle of the current wind
+    pub needle: Color,
+    pub ok: Color,
+    pub unknown: Color,
+    pub warning: Color,
+    pub critical: Color,
+}
+
+// The dark theme, also the base of themes that don't name one
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            background: Color::Black,
+            foreground: Color::White,
+            highlight: Color::LightBlue,
+            border: Color::Blue,
+            selection: Color::DarkGray,
+            trend: Color::Cyan,
+            series: [
+                Color::LightRed,
+                Color::LightBlue,
+                Color::LightGreen,
+                Color::Yellow,
+                Color::Magenta,
+            ],
+            axis: Color::Gray,
+            needle: Color::Yellow,
+            ok: Color::Green,
+            unknown: Color::DarkGray,
+            warning: Color::Yellow,
+            critical: Color::Red,
+        }
+    }
+}
+
+// What a panel shows in one of its rows
+#[derive(Clone, Copy)]
+pub enum PanelItem {
+    Field(&'static FieldInfo),
+    ConnectionStatus,
+    // Running status that turns off once the fan is offline, unlike the
+    // vsd_running field
+    RunningStatus,
+    MainState,
+    Inversion,
+    FarDifference,
+    FrostEvent,
+    // Compass of the current wind, switched to the wind rose with [r]
+    Wind,
+    WindRose,
+}
+
+#[derive(Clone)]
+pub struct Panel {
+    // {window} is replaced by the history window, e.g. "Last 6h"
+    pub title: String,
+    pub items: Vec<PanelItem>,
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub theme: Theme,
+    pub keymap: Keymap,
+    // Device name, serial number or MAC to the group it's listed in
+    pub groups: HashMap<String, String>,
+    // When devices count as late, stale or offline
+    pub staleness: StalenessRules,
+    // First day of the frost season run hours are counted for
+    pub season_start: SeasonStart,
+    fan_panels: Vec<Panel>,
+    weather_station_panels: Vec<Panel>,
+    temperature_probe_panels: Vec<Panel>,
+}
+
+impl Config {
+    // Detail view panels of a device layout in display order
+    pub fn get_panels(&self, layout: Layout) -> &[Panel] {
+        match layout {
+            Layout::Fan => &self.fan_panels,
+            Layout::WeatherStation => &self.weather_station_panels,
+            Layout::TemperatureProbe => &self.temperature_probe_panels,
+        }
+    }
+
+    // Moves devices to the groups of the config file and sets their staleness
+    // policies, which may depend on the group
+    pub fn apply_to_devices(&self, devices: &mut [Device]) {
+        apply_config_groups(devices, &self.groups);
+        for device in devices {
+            device.staleness = self.staleness.get_policy(device);
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    theme: Option<String>,
+    themes: HashMap<String, ThemeConfig>,
+    panels: PanelsConfig,
+    // Action name to the keys replacing its default keys
+    keymap: HashMap<String, Vec<String>>,
+    // Group name to the devices in it
+    groups: HashMap<String, Vec<String>>,
+    staleness: StalenessConfig,
+    // Month and day as "MM-DD", e.g. "07-01"
+    season_start: Option<String>,
+}
+
+// Durations such as "90s", "5m" or "2h", left out ones use the built-in timeouts
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct StalenessConfig {
+    late: Option<String>,
+    stale: Option<String>,
+    offline: Option<String>,
+    // Group name to its timeouts
+    groups: HashMap<String, TimeoutsConfig>,
+    // Device name, serial number or MAC to its timeouts
+    devices: HashMap<String, TimeoutsConfig>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TimeoutsConfig {
+    late: Option<String>,
+    stale: Option<String>,
+    offline: Option<String>,
+}
+
+// Colors left out are taken from the base theme, dark unless named
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeConfig {
+    base: Option<String>,
+    background: Option<String>,
+    foreground: Option<String>,
+    highlight: Option<String>,
+    border: Option<String>,
+    selection: Option<String>,
+    trend: Option<String>,
+    series: Option<Vec<String>>,
+    axis: Option<String>,
+    needle: Option<String>,
+    ok: Option<String>,
+    unknown: Option<String>,
+    warning: Option<String>,
+    critical: Option<String>,
+}
+
+// A layout given in the config file replaces all of its default panels
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct PanelsConfig {
+    fan: Option<Vec<PanelConfig>>,
+    weather_station: Option<Vec<PanelConfig>>,
+    temperature_probe: Option<Vec<PanelConfig>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PanelConfig {
+    title: String,
+    fields: Vec<String>,
+}
+
+// $XDG_CONFIG_HOME/agi-tui/config.toml, falling back to ~/.config
+pub fn get_default_config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("agi-tui").join("config.toml"))
+}
+
+// Without a path the default config file is read when there is one
+pub fn load_config(path: Option<&str>) -> Result<Config, String> {
+    let contents = match path {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .map_err(|error| format!("Failed reading config file {path}: {error}"))?,
+        ),
+        None => match get_default_config_path() {
+            Some(path) if path.exists() => Some(fs::read_to_string(&path).map_err(|error| {
+                format!("Failed reading config file {}: {error}", path.display())
+            })?),
+            _ => None,
+        },
+    };
+    parse_config(contents.as_deref().unwrap_or_default())
+}
+
+pub fn parse_config(contents: &str) -> Result<Config, String> {
+    let defaults: ConfigFile = toml::from_str(DEFAULT_CONFIG)
+        .map_err(|error| format!("Invalid built-in config: {error}"))?;
+    let config_file: ConfigFile =
+        toml::from_str(contents).map_err(|error| format!("Invalid config file: {error}"))?;
+
+    let mut themes = defaults.themes;
+    themes.extend(config_file.themes);
+    let theme_name = config_file
+        .theme
+        .or(defaults.theme)
+        .unwrap_or_else(|| String::from("dark"));
+    let theme = get_theme(&themes, &theme_name)?;
+
+    let get_panels = |panels: Option<Vec<PanelConfig>>, default: Option<Vec<PanelConfig>>| {
+        parse_panels(panels.or(default).unwrap_or_default())
+    };
+    Ok(Config {
+        theme,
+        keymap: parse_keymap(&config_file.keymap)?,
+        groups: parse_groups(&config_file.groups)?,
+        staleness: parse_staleness(&config_file.staleness)?,
+        season_start: match config_file.season_start {
+            Some(season_start) => parse_season_start(&season_start)?,
+            None => SeasonStart::default(),
+        },
+        fan_panels: get_panels(config_file.panels.fan, defaults.panels.fan)?,
+        weather_station_panels: get_panels(
+            config_file.panels.weather_station,
+            defaults.panels.weather_station,
+        )?,
+        temperature_probe_panels: get_panels(
+            config_file.panels.temperature_probe,
+            defaults.panels.temperature_probe,
+        )?,
+    })
+}
+
+// Parses the first day of a season such as "07-01", February 29th is
+// rejected since most years don't have one
+fn parse_season_start(value: &str) -> Result<SeasonStart, String> {
+    let error =
+        || format!("Invalid season start \"{value}\", expected month and day as e.g. \"07-01\"");
+    let (month, day) = value.trim().split_once('-').ok_or_else(error)?;
+    let month: u32 = month.parse().map_err(|_| error())?;
+    let day: u32 = day.parse().map_err(|_| error())?;
+    match NaiveDate::from_ymd_opt(2001, month, day) {
+        Some(_) => Ok(SeasonStart { month, day }),
+        None => Err(error()),
+    }
+}
+
+// A device can only be listed in one group
+fn parse_groups(groups: &HashMap<String, Vec<String>>) -> Result<HashMap<String, String>, String> {
+    let mut names: Vec<&String> = groups.keys().collect();
+    names.sort();
+    let mut members = HashMap::new();
+    for name in names {
+        for device in &groups[name] {
+            if let Some(other) = members.insert(device.clone(), name.clone()) {
+                return Err(format!(
+                    "Device \"{device}\" is in both groups \"{other}\" and \"{name}\""
+                ));
+            }
+        }
+    }
+    Ok(members)
+}
+
+// Timeouts given for the defaults, a group or a device have to be in order,
+// the ones left out move to stay in order with them
+fn parse_staleness(config: &StalenessConfig) -> Result<StalenessRules, String> {
+    let timeouts = TimeoutsConfig {
+        late: config.late.clone(),
+        stale: config.stale.clone(),
+        offline: config.offline.clone(),
+    };
+    let default = parse_timeouts(&timeouts, StalenessPolicy::default())
+        .map_err(|error| format!("Staleness: {error}"))?
+        .apply(StalenessPolicy::default());
+    let mut groups = HashMap::new();
+    for (name, timeouts) in &config.groups {
+        let timeouts = parse_timeouts(timeouts, default)
+            .map_err(|error| format!("Staleness of group \"{name}\": {error}"))?;
+        groups.insert(name.clone(), timeouts);
+    }
+    let mut devices = HashMap::new();
+    for (name, timeouts) in &config.devices {
+        let timeouts = parse_timeouts(timeouts, default)
+            .map_err(|error| format!("Staleness of device \"{name}\": {error}"))?;
+        devices.insert(name.clone(), timeouts);
+    }
+    Ok(StalenessRules {
+        default,
+        groups,
+        devices,
+    })
+}
+
+fn parse_timeouts(
+    timeouts: &TimeoutsConfig,
+    policy: StalenessPolicy,
+) -> Result<PolicyOverride, String> {
+    let get_seconds = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|value| parse_duration(value).map(|duration| duration.num_seconds()))
+            .transpose()
+    };
+    let timeouts = PolicyOverride {
+        late: get_seconds(&timeouts.late)?,
+        stale: get_seconds(&timeouts.stale)?,
+        offline: get_seconds(&timeouts.offline)?,
+    };
+    timeouts.apply(policy).validate()?;
+    Ok(timeouts)
+}
+
+// Applies a theme on top of its chain of base themes
+fn get_theme(themes: &HashMap<String, ThemeConfig>, name: &str) -> Result<Theme, String> {
+    let mut chain = Vec::new();
+    let mut next = Some(name);
+    while let Some(name) = next {
+        if chain.len() > themes.len() {
+            return Err(format!("Theme \"{name}\": base themes form a loop"));
+        }
+        match themes.get(name) {
+            Some(theme) => {
+                chain.push((name, theme));
+                next = theme.base.as_deref();
+            }
+            // Dark is built in, the config may still override its colors
+            None if name == "dark" => break,
+            None => return Err(format!("Unknown theme \"{name}\"")),
+        }
+    }
+    let mut theme = Theme::default();
+    for (name, config) in chain.iter().rev() {
+        apply_theme(&mut theme, config).map_err(|error| format!("Theme \"{name}\": {error}"))?;
+    }
+    Ok(theme)
+}
+
+fn apply_theme(theme: &mut Theme, config: &ThemeConfig) -> Result<(), String> {
+    let colors = [
+        (&mut theme.background, &config.background),
+        (&mut theme.foreground, &config.foreground),
+        (&mut theme.highlight, &config.highlight),
+        (&mut theme.border, &config.border),
+        (&mut theme.selection, &config.selection),
+        (&mut theme.trend, &config.trend),
+        (&mut theme.axis, &config.axis),
+        (&mut theme.needle, &config.needle),
+        (&mut theme.ok, &config.ok),
+        (&mut theme.unknown, &config.unknown),
+        (&mut theme.warning, &config.warning),
+        (&mut theme.critical, &config.critical),
+    ];
+    for (color, name) in colors {
+        if let Some(name) = name {
+            *color = parse_color(name)?;
+        }
+    }
+    if let Some(names) = &config.series {
+        if names.is_empty() {
+            return Err(String::from("series needs at least one color"));
+        }
+        let colors = names
+            .iter()
+            .map(|name| parse_color(name))
+            .collect::<Result<Vec<Color>, String>>()?;
+        for (index, color) in theme.series.iter_mut().enumerate() {
+            *color = colors[index % colors.len()];
+        }
+    }
+    Ok(())
+}
+
+// Named terminal colors, "#rrggbb" or a 256 color palette index
+fn parse_color(name: &str) -> Result<Color, String> {
+    let color = match name.to_lowercase().replace(['_', '-', ' '], "").as_str() {
+        "reset" | "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.starts_with('#') && hex.len() == 7 && hex.is_ascii() => {
+            let channel = |range| u8::from_str_radix(&hex[range], 16);
+            match (channel(1..3), channel(3..5), channel(5..7)) {
+                (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                _ => return Err(format!("invalid color \"{name}\"")),
+            }
+        }
+        index => index
+            .parse::<u8>()
+            .map(Color::Indexed)
+            .map_err(|_| format!("invalid color \"{name}\""))?,
+    };
+    Ok(color)
+}
+
+fn parse_panels(panels: Vec<PanelConfig>) -> Result<Vec<Panel>, String> {
+    let mut parsed = Vec::new();
+    for panel in panels {
+        let items = panel
+            .fields
+            .iter()
+            .map(|field| parse_panel_item(field))
+            .collect::<Result<Vec<PanelItem>, String>>()
+            .map_err(|error| format!("Panel \"{}\": {error}", panel.title))?;
+        parsed.push(Panel {
+            title: panel.title,
+            items,
+        });
+    }
+    Ok(parsed)
+}
+
+// FanData field keys plus the values and widgets derived from them
+fn parse_panel_item(key: &str) -> Result<PanelItem, String> {
+    let item = match key {
+        "connection_status" => PanelItem::ConnectionStatus,
+        "running_status" => PanelItem::RunningStatus,
+        "main_state" => PanelItem::MainState,
+        "inversion" => PanelItem::Inversion,
+        "far_difference" => PanelItem::FarDifference,
+        "frost_event" => PanelItem::FrostEvent,
+        "wind" => PanelItem::Wind,
+        "wind_rose" => PanelItem::WindRose,
+        "air_temperature" => PanelItem::Field(&AIR_TEMPERATURE),
+        key => match FIELDS.iter().find(|field| field.key == key) {
+            Some(field) => PanelItem::Field(field),
+            None => return Err(format!("unknown field \"{key}\"")),
+        },
+    };
+    Ok(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_color_names_hex_and_indexes() {
+        assert_eq!(parse_color("LightBlue"), Ok(Color::LightBlue));
+        assert_eq!(parse_color("dark_gray"), Ok(Color::DarkGray));
+        assert_eq!(parse_color("light-red"), Ok(Color::LightRed));
+        assert_eq!(parse_color("#af5F00"), Ok(Color::Rgb(0xaf, 0x5f, 0x00)));
+        assert_eq!(parse_color("208"), Ok(Color::Indexed(208)));
+    }
+
+    #[test]
+    fn rejects_invalid_colors() {
+        for name in ["", "purple", "#af5f0", "#gg0000", "256", "#aébcd"] {
+            assert!(parse_color(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn themes_apply_on_top_of_their_base() {
+        let config = parse_config(
+            r##"
+theme = "orchard"
+
+[themes.orchard]
+base = "light"
+border = "#005f87"
+series = ["red", "blue"]
+"##,
+        )
+        .unwrap();
+        let theme = config.theme;
+        assert_eq!(theme.border, Color::Rgb(0x00, 0x5f, 0x87));
+        // Taken from the built-in light theme
+        assert_eq!(theme.background, Color::White);
+        assert_eq!(theme.axis, Color::DarkGray);
+        // Shorter series repeat
+        assert_eq!(
+            theme.series,
+            [Color::Red, Color::Blue, Color::Red, Color::Blue, Color::Red]
+        );
+    }
+
+    #[test]
+    fn the_light_theme_draws_no_yellow() {
+        let theme = parse_config("theme = \"light\"").unwrap().theme;
+        assert!(!theme.series.contains(&Color::Yellow));
+        assert_ne!(theme.needle, Color::Yellow);
+        assert_ne!(theme.warning, Color::Yellow);
+    }
+
+    #[test]
+    fn rejects_invalid_themes() {
+        let errors = [
+            ("theme = \"solar\"", "Unknown theme \"solar\""),
+            (
+                "[themes.dark]\nborder = \"purple\"",
+                "Theme \"dark\": invalid color \"purple\"",
+            ),
+            (
+                "[themes.dark]\nseries = []",
+                "Theme \"dark\": series needs at least one color",
+            ),
+            (
+                "theme = \"a\"\n[themes.a]\nbase = \"b\"\n[themes.b]\nbase = \"a\"",
+                "Theme \"a\": base themes form a loop",
+            ),
+        ];
+        for (contents, error) in errors {
+            assert_eq!(parse_config(contents).err().as_deref(), Some(error));
+        }
+    }
+
+    #[test]
+    fn panels_of_a_layout_replace_the_defaults() {
+        let config = parse_config(
+            r#"
+[[panels.fan]]
+title = "Temperature"
+fields = ["temperature_top", "inversion"]
+"#,
+        )
+        .unwrap();
+        let panels = config.get_panels(Layout::Fan);
+        assert_eq!(panels.len(), 1);
+        assert_eq!(panels[0].items.len(), 2);
+        assert_eq!(config.get_panels(Layout::WeatherStation).len(), 5);
+        let error = parse_config("[[panels.fan]]\ntitle = \"A\"\nfields = [\"pressure\"]");
+        assert!(error.err().unwrap().starts_with("Panel \"A\": "));
+    }
+
+    #[test]
+    fn parses_season_start() {
+        assert_eq!(
+            parse_season_start("07-01"),
+            Ok(SeasonStart { month: 7, day: 1 })
+        );
+        assert_eq!(
+            parse_season_start("12-31"),
+            Ok(SeasonStart { month: 12, day: 31 })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_season_starts() {
+        for value in ["", "07", "7/1", "13-01", "04-31", "02-29", "00-10", "-1-01"] {
+            assert!(parse_season_start(value).is_err(), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn seasons_default_to_calendar_years() {
+        let config = parse_config("").unwrap();
+        assert_eq!(config.season_start, SeasonStart::default());
+        let config = parse_config("season_start = \"05-15\"").unwrap();
+        assert_eq!(config.season_start, SeasonStart { month: 5, day: 15 });
+    }
+
+    #[test]
+    fn parses_groups_by_device() {
+        let groups = HashMap::from([
+            (
+                String::from("North Cherries"),
+                vec![String::from("SN0001"), String::from("North Cherries 2")],
+            ),
+            (
+                String::from("River Flats"),
+                vec![String::from("aa:bb:cc:dd:ee:ff")],
+            ),
+        ]);
+        let members = parse_groups(&groups).unwrap();
+        assert_eq!(members.len(), 3);
+        assert_eq!(members["SN0001"], "North Cherries");
+        assert_eq!(members["North Cherries 2"], "North Cherries");
+        assert_eq!(members["aa:bb:cc:dd:ee:ff"], "River Flats");
+    }
+
+    #[test]
+    fn rejects_devices_in_two_groups() {
+        let groups = HashMap::from([
+            (String::from("North Cherries"), vec![String::from("SN0001")]),
+            (String::from("River Flats"), vec![String::from("SN0001")]),
+        ]);
+        // Groups are checked in name order, so the error doesn't depend on the map
+        assert_eq!(
+            parse_groups(&groups),
+            Err(String::from(
+                "Device \"SN0001\" is in both groups \"North Cherries\" and \"River Flats\""
+            ))
+        );
+    }
+
+    #[test]
+    fn staleness_defaults_to_offline_after_ten_minutes() {
+        let staleness = parse_config("").unwrap().staleness;
+        let minutes = 10 * 60;
+        assert_eq!(
+            staleness.default,
+            StalenessPolicy {
+                late: minutes,
+                stale: minutes,
+                offline: minutes,
+            }
+        );
+    }
+
+    #[test]
+    fn parses_staleness_timeouts() {
+        let staleness = parse_config(
+            r#"
+[staleness]
+late = "2m"
+stale = "5m"
+offline = "30m"
+
+[staleness.groups."River Flats"]
+late = "10m"
+stale = "20m"
+
+[staleness.devices."Weather Station"]
+offline = "2h"
+"#,
+        )
+        .unwrap()
+        .staleness;
+        assert_eq!(staleness.default.get_summary(), "2m/5m/30m");
+        let group = staleness.groups["River Flats"].apply(staleness.default);
+        assert_eq!(group.get_summary(), "10m/20m/30m");
+        let device = staleness.devices["Weather Station"].apply(staleness.default);
+        assert_eq!(device.get_summary(), "2m/5m/2h");
+    }
+
+    #[test]
+    fn staleness_stages_left_out_move_with_the_ones_given() {
+        let staleness = parse_config("[staleness]\nstale = \"5m\"")
+            .unwrap()
+            .staleness;
+        assert_eq!(staleness.default.get_summary(), "5m/5m/10m");
+        let staleness = parse_config("[staleness]\nlate = \"20m\"")
+            .unwrap()
+            .staleness;
+        assert_eq!(staleness.default.get_summary(), "20m/20m/20m");
+    }
+
+    #[test]
+    fn rejects_invalid_staleness_timeouts() {
+        let error = |contents| parse_config(contents).err().unwrap();
+        assert_eq!(
+            error("[staleness]\nlate = \"5m\"\nstale = \"2m\""),
+            "Staleness: late must not be longer than stale"
+        );
+        assert_eq!(
+            error("[staleness.groups.North]\nstale = \"1h\"\noffline = \"30m\""),
+            "Staleness of group \"North\": stale must not be longer than offline"
+        );
+        assert!(error("[staleness.devices.SN0001]\nlate = \"soon\"")
+            .starts_with("Staleness of device \"SN0001\": "));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::fan_data::FanData;
+use crate::schema::{ColumnSpec, FAN_TABLE_COLUMNS, PROBE_TABLE_COLUMNS, WEATHER_TABLE_COLUMNS};
+use crate::sqlite::{fan_data_from_row, probe_data_from_row, weather_data_from_row};
+use rusqlite::Row;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+// Detail view a device type is shown with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    Fan,
+    WeatherStation,
+    TemperatureProbe,
+}
+
+// Every device type the app can read. Fans are type 1 with FanN tables as
+// written by the homebase service. Weather stations and probes aren't part of
+// that schema, type 2 with WeatherN tables and type 3 with ProbeN tables are
+// assumptions of this app, see the README. All types share the FanN column
+// names.
+pub struct DeviceType {
+    // Value of devices.type
+    pub id: i64,
+    pub name: &'static str,
+    // Name of the tables holding this type's readings before their number,
+    // devices are only mapped to tables of their own type
+    pub table_prefix: &'static str,
+    // Columns the data tables are checked for by --check-db
+    pub columns: &'static [ColumnSpec],
+    // Decodes a row of a data table, reading only this type's columns
+    pub reader: fn(&Row) -> Result<FanData, rusqlite::Error>,
+    pub layout: Layout,
+}
+
+pub static DEVICE_TYPES: [DeviceType; 3] = [
+    DeviceType {
+        id: 1,
+        name: "Fan",
+        table_prefix: "Fan",
+        columns: &FAN_TABLE_COLUMNS,
+        reader: fan_data_from_row,
+        layout: Layout::Fan,
+    },
+    DeviceType {
+        id: 2,
+        name: "Weather Station",
+        table_prefix: "Weather",
+        columns: &WEATHER_TABLE_COLUMNS,
+        reader: weather_data_from_row,
+        layout: Layout::WeatherStation,
+    },
+    DeviceType {
+        id: 3,
+        name: "Temperature Probe",
+        table_prefix: "Probe",
+        columns: &PROBE_TABLE_COLUMNS,
+        reader: probe_data_from_row,
+        layout: Layout::TemperatureProbe,
+    },
+];
+
+pub fn get_device_type(id: i64) -> Option<&'static DeviceType> {
+    DEVICE_TYPES.iter().find(|device_type| device_type.id == id)
+}
+
+impl DeviceType {
+    // GLOB pattern of this type's data tables, e.g. Fan[0-9]*
+    pub fn get_table_pattern(&self) -> String {
+        format!("{}[0-9]*", self.table_prefix)
+    }
+
+    // Number of one of this type's data tables, e.g. 2 for Fan2
+    pub fn get_table_number(&self, table: &str) -> Option<u32> {
+        table.strip_prefix(self.table_prefix)?.parse().ok()
+    }
+}
+
+impl fmt::Debug for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DeviceType")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+// Devices serialize their type by name
+impl Serialize for DeviceType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
@@ -0,0 +1,259 @@
+use crate::fan_data::{FanData, FieldValue, Reading, FIELDS};
+use crate::sqlite::Device;
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+// Column name and value pairs of one exported row
+type Record = Vec<(String, Value)>;
+
+// File name in the working directory, e.g. agi-export-River_Flats-20221018-053000.csv
+pub fn get_default_export_path(name: &str, format: ExportFormat) -> PathBuf {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let timestamp = chrono::offset::Local::now().format("%Y%m%d-%H%M%S");
+    PathBuf::from(format!(
+        "agi-export-{}-{}.{}",
+        name,
+        timestamp,
+        format.extension()
+    ))
+}
+
+// Latest reading of every device, one row per device
+pub fn export_snapshot(
+    path: &Path,
+    devices: &[Device],
+    overview: &[Option<FanData>],
+    format: ExportFormat,
+) -> io::Result<usize> {
+    let records: Vec<Record> = devices
+        .iter()
+        .zip(overview)
+        .map(|(device, fan_data)| get_record(device, fan_data.as_ref()))
+        .collect();
+    write_records(path, &records, format)?;
+    Ok(records.len())
+}
+
+// Every reading of one device, one row per reading
+pub fn export_history(
+    path: &Path,
+    device: &Device,
+    history: &[FanData],
+    format: ExportFormat,
+) -> io::Result<usize> {
+    let records: Vec<Record> = history
+        .iter()
+        .map(|fan_data| get_record(device, Some(fan_data)))
+        .collect();
+    write_records(path, &records, format)?;
+    Ok(records.len())
+}
+
+fn get_record(device: &Device, fan_data: Option<&FanData>) -> Record {
+    let mut record = vec![
+        (String::from("Device"), Value::from(device.name.clone())),
+        (
+            String::from("Serial Number"),
+            Value::from(device.serial_number.clone()),
+        ),
+    ];
+    for field in &FIELDS {
+        let value = match fan_data {
+            Some(fan_data) => match (field.value)(fan_data) {
+                FieldValue::Number(Reading::Ok(value)) => get_number_value(value),
+                FieldValue::Number(Reading::Disabled) => Value::from("Disabled"),
+                FieldValue::Number(Reading::Error) => Value::from("Error"),
+                FieldValue::Number(Reading::Missing) => Value::Null,
+                FieldValue::Text(text) => Value::from(text),
+            },
+            None => Value::Null,
+        };
+        record.push((field.get_column_name(), value));
+    }
+    record
+}
+
+// Goes through the shortest decimal form so 9.7 isn't written as 9.699999809
+fn get_number_value(value: f32) -> Value {
+    match value.to_string().parse::<f64>() {
+        Ok(value) => Value::from(value),
+        Err(_) => Value::Null,
+    }
+}
+
+fn write_records(path: &Path, records: &[Record], format: ExportFormat) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    match format {
+        ExportFormat::Json => {
+            let rows: Vec<Map<String, Value>> = records
+                .iter()
+                .map(|record| record.iter().cloned().collect())
+                .collect();
+            serde_json::to_writer_pretty(&mut out, &rows)?;
+            writeln!(out)?;
+        }
+        ExportFormat::Csv => {
+            let headers: Vec<String> = get_record_headers()
+                .iter()
+                .map(|name| escape_csv(name))
+                .collect();
+            writeln!(out, "{}", headers.join(","))?;
+            for record in records {
+                let values: Vec<String> = record
+                    .iter()
+                    .map(|(_, value)| match value {
+                        Value::Null => String::new(),
+                        Value::String(text) => escape_csv(text),
+                        value => value.to_string(),
+                    })
+                    .collect();
+                writeln!(out, "{}", values.join(","))?;
+            }
+        }
+    }
+    out.flush()
+}
+
+fn get_record_headers() -> Vec<String> {
+    let mut headers = vec![String::from("Device"), String::from("Serial Number")];
+    headers.extend(FIELDS.iter().map(|field| field.get_column_name()));
+    headers
+}
+
+// Quotes values holding a separator, quote or line break, doubling quotes
+pub fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::DEVICE_TYPES;
+    use crate::staleness::StalenessPolicy;
+    use std::fs;
+
+    fn get_device(name: &str) -> Device {
+        Device {
+            name: String::from(name),
+            serial_number: String::from("SN0001"),
+            mac: None,
+            device_type: &DEVICE_TYPES[0],
+            table: Some(String::from("Fan1")),
+            table_source: None,
+            group: None,
+            staleness: StalenessPolicy::default(),
+        }
+    }
+
+    fn get_reading() -> FanData {
+        FanData {
+            temperature_top: Reading::Ok(9.7),
+            temperature_bottom: Reading::Disabled,
+            temperature_far: Reading::Error,
+            ..FanData::new(String::from("2024-05-01 00:00:00"))
+        }
+    }
+
+    // Writes an export to a file of its own and reads it back
+    fn export(
+        name: &str,
+        format: ExportFormat,
+        write: impl Fn(&Path) -> io::Result<usize>,
+    ) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "agi-tui-test-{}-{}.{}",
+            std::process::id(),
+            name,
+            format.extension()
+        ));
+        write(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        contents
+    }
+
+    #[test]
+    fn escapes_separators_quotes_and_line_breaks() {
+        assert_eq!(escape_csv("North Cherries 1"), "North Cherries 1");
+        assert_eq!(escape_csv(""), "");
+        assert_eq!(escape_csv("Block 3, north"), "\"Block 3, north\"");
+        assert_eq!(escape_csv("The \"big\" fan"), "\"The \"\"big\"\" fan\"");
+        assert_eq!(escape_csv("two\nlines"), "\"two\nlines\"");
+        assert_eq!(escape_csv("two\r\nlines"), "\"two\r\nlines\"");
+    }
+
+    #[test]
+    fn records_start_with_the_device_and_follow_the_fields() {
+        let record = get_record(&get_device("North"), Some(&get_reading()));
+        let headers = get_record_headers();
+        assert_eq!(record.len(), headers.len());
+        assert!(record
+            .iter()
+            .zip(&headers)
+            .all(|((name, _), header)| name == header));
+        let get = |name: &str| &record.iter().find(|(key, _)| key == name).unwrap().1;
+        assert_eq!(get("Device"), &Value::from("North"));
+        assert_eq!(get("Serial Number"), &Value::from("SN0001"));
+        assert_eq!(get("Temperature Top (℃)"), &Value::from(9.7));
+        assert_eq!(get("Temperature Bottom (℃)"), &Value::from("Disabled"));
+        assert_eq!(get("Temperature Far (℃)"), &Value::from("Error"));
+        assert_eq!(get("Humidity (%)"), &Value::Null);
+    }
+
+    #[test]
+    fn devices_without_a_reading_export_empty_values() {
+        let record = get_record(&get_device("North"), None);
+        assert!(record[2..].iter().all(|(_, value)| value.is_null()));
+    }
+
+    #[test]
+    fn csv_exports_quote_device_names() {
+        let devices = [get_device("Block 3, \"north\"\nrow")];
+        let overview = [Some(get_reading())];
+        let contents = export("csv", ExportFormat::Csv, |path| {
+            export_snapshot(path, &devices, &overview, ExportFormat::Csv)
+        });
+        let (header, row) = contents.split_once('\n').unwrap();
+        assert!(header.starts_with("Device,Serial Number,"));
+        assert!(row.starts_with("\"Block 3, \"\"north\"\"\nrow\",SN0001,"));
+        assert!(row.contains(",9.7,Disabled,Error,"), "{row}");
+    }
+
+    #[test]
+    fn json_exports_keep_names_as_written() {
+        let device = get_device("Block 3, \"north\"");
+        let history = [get_reading(), get_reading()];
+        let contents = export("json", ExportFormat::Json, |path| {
+            export_history(path, &device, &history, ExportFormat::Json)
+        });
+        let rows: Vec<Map<String, Value>> = serde_json::from_str(&contents).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["Device"], Value::from("Block 3, \"north\""));
+        assert_eq!(rows[0]["Temperature Top (℃)"], Value::from(9.7));
+    }
+}
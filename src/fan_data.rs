@@ -1,117 +1,446 @@
-// #[derive(Clone, Debug)]
-// pub enum FieldState {
-//     Okay = 0,
-//     Error = -50,
-//     Disabled = -49,
-// }
-
-#[derive(Clone, Debug)]
+use crate::staleness::{Staleness, StalenessPolicy};
+use crate::wind::get_cardinal_direction;
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+
+// Sentinel values the homebase service writes in place of a reading
+const DISABLED_SENTINEL: f64 = -49.0;
+const ERROR_SENTINEL: f64 = -50.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reading<T> {
+    Ok(T),
+    Disabled,
+    Error,
+    Missing,
+}
+
+impl Reading<f64> {
+    // Decodes a raw column value, NULL becomes Missing
+    pub fn from_raw(raw: Option<f64>) -> Reading<f64> {
+        match raw {
+            None => Reading::Missing,
+            Some(value) if value == DISABLED_SENTINEL => Reading::Disabled,
+            Some(value) if value == ERROR_SENTINEL => Reading::Error,
+            Some(value) => Reading::Ok(value),
+        }
+    }
+}
+
+impl<T: Copy + Display> Reading<T> {
+    pub fn value(&self) -> Option<T> {
+        match self {
+            Reading::Ok(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Reading<U> {
+        match self {
+            Reading::Ok(value) => Reading::Ok(f(value)),
+            Reading::Disabled => Reading::Disabled,
+            Reading::Error => Reading::Error,
+            Reading::Missing => Reading::Missing,
+        }
+    }
+
+    // Combines two readings, the first reading that isn't Ok decides the result
+    pub fn combine<U: Copy + Display, V>(
+        self,
+        other: Reading<U>,
+        f: impl FnOnce(T, U) -> V,
+    ) -> Reading<V> {
+        match (self, other) {
+            (Reading::Ok(a), Reading::Ok(b)) => Reading::Ok(f(a, b)),
+            (Reading::Disabled, _) | (Reading::Ok(_), Reading::Disabled) => Reading::Disabled,
+            (Reading::Error, _) | (Reading::Ok(_), Reading::Error) => Reading::Error,
+            (Reading::Missing, _) | (Reading::Ok(_), Reading::Missing) => Reading::Missing,
+        }
+    }
+
+    pub fn format_with_unit(&self, unit: &str) -> String {
+        match self {
+            Reading::Ok(value) => format!("{}{}", value, unit),
+            Reading::Disabled => String::from("Disabled"),
+            Reading::Error => String::from("Error"),
+            Reading::Missing => String::from("Missing"),
+        }
+    }
+}
+
+// Valid readings serialize as numbers, Disabled/Error as strings and Missing as null
+impl<T: Serialize> Serialize for Reading<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Reading::Ok(value) => value.serialize(serializer),
+            Reading::Disabled => serializer.serialize_str("Disabled"),
+            Reading::Error => serializer.serialize_str("Error"),
+            Reading::Missing => serializer.serialize_none(),
+        }
+    }
+}
+
+// Format of the datetime column in the FanN tables
+pub const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Clone, Debug, Serialize)]
 pub struct FanData {
-    // Panel Switch & Door Status
-    pub main_panel_open: bool,
-    pub control_door: bool,
-    pub main_switch: bool,
+    // Panel Switch & Door Status, None when the device doesn't report a flag
+    pub main_panel_open: Option<bool>,
+    pub control_door: Option<bool>,
+    pub main_switch: Option<bool>,
 
     // Fan Status
-    pub vsd_error: bool,
-    pub vsd_running: bool,
-    pub vsd_command: bool,
+    pub vsd_error: Option<bool>,
+    pub vsd_running: Option<bool>,
+    pub vsd_command: Option<bool>,
 
     // Temperature Data
-    pub temperature_top: f32,
-    pub temperature_bottom: f32,
-    pub temperature_far: f32,
+    pub temperature_top: Reading<f32>,
+    pub temperature_bottom: Reading<f32>,
+    pub temperature_far: Reading<f32>,
 
     // Fan Information
-    pub signal_strength: f32,
+    pub signal_strength: Reading<f32>,
     pub control_method: u8,
-    pub battery_voltage: f32,
+    pub battery_voltage: Reading<f32>,
     pub last_update: String,
 
     // Fan Data
-    pub motor_vibration: f32,
-    pub motor_current: f32,
+    pub motor_vibration: Reading<f32>,
+    pub motor_current: Reading<f32>,
     // main_state: bool,
 
     // Environmental Data
-    pub humidity: f32,
-    pub wind_speed: f32,
-    pub wind_direction: i16,
-    pub rain_meter: f32,
+    pub humidity: Reading<f32>,
+    pub wind_speed: Reading<f32>,
+    pub wind_direction: Reading<i16>,
+    pub rain_meter: Reading<f32>,
 }
 
+// Typed value of a field, used for exports
+pub enum FieldValue {
+    Number(Reading<f32>),
+    Text(String),
+}
+
+// Metadata shared by the dashboard strings, the CLI and exports
+pub struct FieldInfo {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub unit: &'static str,
+    pub value: fn(&FanData) -> FieldValue,
+    pub display: fn(&FanData) -> String,
+    // Expected (min, max) of a bounded number, shown as a gauge on the dashboard
+    pub range: Option<(f32, f32)>,
+    // Whether the dashboard plots recent readings of the field as a sparkline
+    pub trend: bool,
+}
+
+impl FieldInfo {
+    // Human readable column name, e.g. "Temperature Top (℃)"
+    pub fn get_column_name(&self) -> String {
+        if self.unit.is_empty() {
+            self.label.to_string()
+        } else {
+            format!("{} ({})", self.label, self.unit)
+        }
+    }
+}
+
+pub const LAST_UPDATE: FieldInfo = FieldInfo {
+    key: "last_update",
+    label: "Last Update",
+    unit: "",
+    value: |d| FieldValue::Text(d.last_update.clone()),
+    display: |d| d.last_update.clone(),
+    range: None,
+    trend: false,
+};
+pub const CONTROL_METHOD: FieldInfo = FieldInfo {
+    key: "control_method",
+    label: "Control Method",
+    unit: "",
+    value: |d| FieldValue::Text(d.get_operating_mode_string()),
+    display: FanData::get_operating_mode_string,
+    range: None,
+    trend: false,
+};
+pub const TEMPERATURE_TOP: FieldInfo = FieldInfo {
+    key: "temperature_top",
+    label: "Temperature Top",
+    unit: "℃",
+    value: |d| FieldValue::Number(d.temperature_top),
+    display: FanData::get_temperature_top_string,
+    range: None,
+    trend: true,
+};
+pub const TEMPERATURE_BOTTOM: FieldInfo = FieldInfo {
+    key: "temperature_bottom",
+    label: "Temperature Bottom",
+    unit: "℃",
+    value: |d| FieldValue::Number(d.temperature_bottom),
+    display: FanData::get_temperature_bottom_string,
+    range: None,
+    trend: true,
+};
+pub const TEMPERATURE_FAR: FieldInfo = FieldInfo {
+    key: "temperature_far",
+    label: "Temperature Far",
+    unit: "℃",
+    value: |d| FieldValue::Number(d.temperature_far),
+    display: FanData::get_temperature_far_string,
+    range: None,
+    trend: true,
+};
+pub const BATTERY_VOLTAGE: FieldInfo = FieldInfo {
+    key: "battery_voltage",
+    label: "Battery Voltage",
+    unit: "V",
+    value: |d| FieldValue::Number(d.battery_voltage),
+    display: FanData::get_voltage_string,
+    range: Some((10.5, 14.5)),
+    trend: true,
+};
+pub const SIGNAL_STRENGTH: FieldInfo = FieldInfo {
+    key: "signal_strength",
+    label: "Signal Strength",
+    unit: "dBm",
+    value: |d| FieldValue::Number(d.signal_strength),
+    display: FanData::get_signal_strength_string,
+    range: Some((-120.0, -40.0)),
+    trend: true,
+};
+pub const MOTOR_CURRENT: FieldInfo = FieldInfo {
+    key: "motor_current",
+    label: "Motor Current",
+    unit: "A",
+    value: |d| FieldValue::Number(d.motor_current),
+    display: FanData::get_motor_current_string,
+    range: None,
+    trend: true,
+};
+pub const MOTOR_VIBRATION: FieldInfo = FieldInfo {
+    key: "motor_vibration",
+    label: "Motor Vibration",
+    unit: "mm/s",
+    value: |d| FieldValue::Number(d.motor_vibration),
+    display: FanData::get_motor_vibration_string,
+    range: None,
+    trend: true,
+};
+pub const READY_STATUS: FieldInfo = FieldInfo {
+    key: "vsd_ready",
+    label: "Ready Status",
+    unit: "",
+    value: |d| FieldValue::Text(d.get_ready_string()),
+    display: FanData::get_ready_string,
+    range: None,
+    trend: false,
+};
+pub const RUNNING: FieldInfo = FieldInfo {
+    key: "vsd_running",
+    label: "Running Status",
+    unit: "",
+    value: |d| FieldValue::Text(d.get_running_string()),
+    display: FanData::get_running_string,
+    range: None,
+    trend: false,
+};
+pub const COMMAND: FieldInfo = FieldInfo {
+    key: "vsd_command",
+    label: "C&M Command",
+    unit: "",
+    value: |d| FieldValue::Text(d.get_command_string()),
+    display: FanData::get_command_string,
+    range: None,
+    trend: false,
+};
+pub const MAIN_SWITCH: FieldInfo = FieldInfo {
+    key: "main_switch",
+    label: "Main Switch",
+    unit: "",
+    value: |d| FieldValue::Text(d.get_main_switch_string()),
+    display: FanData::get_main_switch_string,
+    range: None,
+    trend: false,
+};
+pub const MAIN_PANEL_DOOR: FieldInfo = FieldInfo {
+    key: "main_panel_door",
+    label: "Main Panel Door",
+    unit: "",
+    value: |d| FieldValue::Text(d.get_main_panel_string()),
+    display: FanData::get_main_panel_string,
+    range: None,
+    trend: false,
+};
+pub const CONTROL_DOOR: FieldInfo = FieldInfo {
+    key: "control_door",
+    label: "C&M Panel Door",
+    unit: "",
+    value: |d| FieldValue::Text(d.get_control_door_string()),
+    display: FanData::get_control_door_string,
+    range: None,
+    trend: false,
+};
+pub const HUMIDITY: FieldInfo = FieldInfo {
+    key: "humidity",
+    label: "Humidity",
+    unit: "%",
+    value: |d| FieldValue::Number(d.humidity),
+    display: FanData::get_humidity_string,
+    range: Some((0.0, 100.0)),
+    trend: true,
+};
+pub const WIND_SPEED: FieldInfo = FieldInfo {
+    key: "wind_speed",
+    label: "Wind Speed",
+    unit: "m/s",
+    value: |d| FieldValue::Number(d.wind_speed),
+    display: FanData::get_wind_speed_string,
+    range: None,
+    trend: true,
+};
+pub const WIND_DIRECTION: FieldInfo = FieldInfo {
+    key: "wind_direction",
+    label: "Wind Direction",
+    unit: "°",
+    value: |d| FieldValue::Number(d.wind_direction.map(f32::from)),
+    display: FanData::get_wind_direction_string,
+    range: None,
+    trend: false,
+};
+pub const RAIN_METER: FieldInfo = FieldInfo {
+    key: "rain_meter",
+    label: "Rain Meter",
+    unit: "mm",
+    value: |d| FieldValue::Number(d.rain_meter),
+    display: FanData::get_rain_meter_string,
+    range: None,
+    trend: true,
+};
+
+// Weather stations report their air temperature in the top sensor column
+pub const AIR_TEMPERATURE: FieldInfo = FieldInfo {
+    label: "Air Temperature",
+    ..TEMPERATURE_TOP
+};
+
+// Every field of a reading in dashboard order
+pub static FIELDS: [FieldInfo; 19] = [
+    LAST_UPDATE,
+    CONTROL_METHOD,
+    TEMPERATURE_TOP,
+    TEMPERATURE_BOTTOM,
+    TEMPERATURE_FAR,
+    BATTERY_VOLTAGE,
+    SIGNAL_STRENGTH,
+    MOTOR_CURRENT,
+    MOTOR_VIBRATION,
+    READY_STATUS,
+    RUNNING,
+    COMMAND,
+    MAIN_SWITCH,
+    MAIN_PANEL_DOOR,
+    CONTROL_DOOR,
+    HUMIDITY,
+    WIND_SPEED,
+    WIND_DIRECTION,
+    RAIN_METER,
+];
+
 impl FanData {
-    pub fn get_temperature_top_string(&self) -> String {
-        let value = self.temperature_top;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}℃", value.to_string()),
+    // Reading without any values, device type readers fill in their columns
+    pub fn new(last_update: String) -> FanData {
+        FanData {
+            main_panel_open: None,
+            control_door: None,
+            main_switch: None,
+            vsd_error: None,
+            vsd_running: None,
+            vsd_command: None,
+            temperature_top: Reading::Missing,
+            temperature_bottom: Reading::Missing,
+            temperature_far: Reading::Missing,
+            signal_strength: Reading::Missing,
+            control_method: u8::MAX,
+            battery_voltage: Reading::Missing,
+            last_update,
+            motor_vibration: Reading::Missing,
+            motor_current: Reading::Missing,
+            humidity: Reading::Missing,
+            wind_speed: Reading::Missing,
+            wind_direction: Reading::Missing,
+            rain_meter: Reading::Missing,
         }
     }
 
+    pub fn get_temperature_top_string(&self) -> String {
+        self.temperature_top.format_with_unit(TEMPERATURE_TOP.unit)
+    }
+
     pub fn get_temperature_bottom_string(&self) -> String {
-        let value = self.temperature_bottom;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}℃", value.to_string()),
-        }
+        self.temperature_bottom
+            .format_with_unit(TEMPERATURE_BOTTOM.unit)
     }
 
     pub fn get_temperature_far_string(&self) -> String {
-        let value = self.temperature_far;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}℃", value.to_string()),
-        }
+        self.temperature_far.format_with_unit(TEMPERATURE_FAR.unit)
+    }
+
+    // Inversion strength, warmer air above the fan means a positive value
+    pub fn get_inversion(&self) -> Reading<f32> {
+        self.temperature_top
+            .combine(self.temperature_bottom, get_temperature_difference)
+    }
+
+    // Far sensor relative to the fan-local bottom sensor
+    pub fn get_far_difference(&self) -> Reading<f32> {
+        self.temperature_far
+            .combine(self.temperature_bottom, get_temperature_difference)
+    }
+
+    pub fn get_inversion_string(&self) -> String {
+        self.get_inversion().format_with_unit(TEMPERATURE_TOP.unit)
+    }
+
+    pub fn get_far_difference_string(&self) -> String {
+        self.get_far_difference()
+            .format_with_unit(TEMPERATURE_FAR.unit)
     }
 
     pub fn get_motor_current_string(&self) -> String {
-        let value = self.motor_current;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}A", value.to_string()),
-        }
+        self.motor_current.format_with_unit(MOTOR_CURRENT.unit)
     }
 
     pub fn get_motor_vibration_string(&self) -> String {
-        let value = self.motor_vibration;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}mm/s", value.to_string()),
-        }
+        self.motor_vibration.format_with_unit(MOTOR_VIBRATION.unit)
     }
 
     pub fn get_ready_string(&self) -> String {
         let value = self.vsd_error;
         match value {
-            true => String::from("Error"),
-            false => String::from("Ready"),
+            Some(true) => String::from("Error"),
+            Some(false) => String::from("Ready"),
+            None => String::from("Missing"),
         }
     }
 
     pub fn get_running_string(&self) -> String {
         let value = self.vsd_running;
         match value {
-            true => String::from("Running"),
-            false => String::from("Idle"),
+            Some(true) => String::from("Running"),
+            Some(false) => String::from("Idle"),
+            None => String::from("Missing"),
         }
     }
 
     pub fn get_command_string(&self) -> String {
         let value = self.vsd_command;
         match value {
-            true => String::from("On"),
-            false => String::from("Off"),
+            Some(true) => String::from("On"),
+            Some(false) => String::from("Off"),
+            None => String::from("Missing"),
         }
     }
 
@@ -128,146 +457,151 @@ impl FanData {
     }
 
     pub fn get_humidity_string(&self) -> String {
-        let value = self.humidity;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}%", value.to_string()),
-        }
+        self.humidity.format_with_unit(HUMIDITY.unit)
     }
 
     pub fn get_voltage_string(&self) -> String {
-        let value = self.battery_voltage;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}V", value.to_string()),
-        }
+        self.battery_voltage.format_with_unit(BATTERY_VOLTAGE.unit)
     }
 
     pub fn get_wind_speed_string(&self) -> String {
-        let value = self.wind_speed;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}m/s", value.to_string()),
-        }
+        self.wind_speed.format_with_unit(WIND_SPEED.unit)
     }
 
+    // e.g. 233° SW
     pub fn get_wind_direction_string(&self) -> String {
-        let value = self.wind_direction;
-        match value {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}°", value.to_string()),
+        match self.wind_direction {
+            Reading::Ok(degrees) => format!(
+                "{}{} {}",
+                degrees,
+                WIND_DIRECTION.unit,
+                get_cardinal_direction(degrees)
+            ),
+            reading => reading.format_with_unit(WIND_DIRECTION.unit),
         }
     }
 
     pub fn get_signal_strength_string(&self) -> String {
-        let value = self.signal_strength;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}dBm", value.to_string()),
-        }
+        self.signal_strength.format_with_unit(SIGNAL_STRENGTH.unit)
     }
 
     pub fn get_rain_meter_string(&self) -> String {
-        let value = self.rain_meter;
-        let int_value = value as i16;
-        match int_value as i16 {
-            -49 => String::from("Disabled"),
-            -50 => String::from("Error"),
-            _ => format!("{}mm", value.to_string()),
-        }
+        self.rain_meter.format_with_unit(RAIN_METER.unit)
     }
 
     pub fn get_main_panel_string(&self) -> String {
         let value = self.main_panel_open;
         match value {
-            true => String::from("Closed"),
-            false => String::from("Open"),
+            Some(true) => String::from("Closed"),
+            Some(false) => String::from("Open"),
+            None => String::from("Missing"),
         }
     }
 
     pub fn get_control_door_string(&self) -> String {
         let value = self.control_door;
         match value {
-            true => String::from("Closed"),
-            false => String::from("Open"),
+            Some(true) => String::from("Closed"),
+            Some(false) => String::from("Open"),
+            None => String::from("Missing"),
         }
     }
 
     pub fn get_main_switch_string(&self) -> String {
         let value = self.main_switch;
         match value {
-            true => String::from("On"),
-            false => String::from("Off"),
+            Some(true) => String::from("On"),
+            Some(false) => String::from("Off"),
+            None => String::from("Missing"),
         }
     }
 
     pub fn get_last_update_string(&self) -> String {
-        let seconds_since_last_result = self.get_seconds_since_last();
-        if seconds_since_last_result.is_err() {
-            return String::from("Parsing error");
-        };
-        let difference_seconds = seconds_since_last_result.unwrap();
-        match difference_seconds {
-            difference_seconds if difference_seconds > 60 * 60 * 24 => {
-                let hours = difference_seconds / (60 * 60 * 24);
-                format!("{hours} Days Ago")
-            }
-            difference_seconds if difference_seconds > 60 * 60 => {
-                let hours = difference_seconds / (60 * 60);
-                format!("{hours} Hours Ago")
-            }
-            difference_seconds if difference_seconds < 60 * 60 => {
-                let minutes = difference_seconds / (60);
-                format!("{minutes} Minutes Ago")
-            }
-            _ => String::from("Matching error"),
+        match self.get_seconds_since_last() {
+            Ok(seconds) => format_time_since(seconds),
+            Err(_) => String::from("Parsing error"),
         }
     }
 
-    pub fn get_connection_status_string(&self) -> String {
-        let seconds_since_last_result = self.get_seconds_since_last();
-        if seconds_since_last_result.is_err() {
-            return String::from("Parsing error");
-        };
-        let seconds_since = seconds_since_last_result.unwrap();
-        match seconds_since {
-            seconds_since if seconds_since <= 60 * 10 => String::from("Online"),
-            seconds_since if seconds_since > 60 * 10 => String::from("Offline"),
-            _ => String::from("Matching error"),
+    pub fn get_connection_status_string(&self, policy: &StalenessPolicy) -> String {
+        match self.get_staleness(policy) {
+            Ok(staleness) => staleness.label().to_string(),
+            Err(_) => String::from("Parsing error"),
         }
     }
 
-    pub fn get_running_status_string(&self) -> String {
-        if self.vsd_running == false {
-            return String::from("Not Running");
+    pub fn get_running_status_string(&self, policy: &StalenessPolicy) -> String {
+        match self.is_running(policy) {
+            Ok(Some(true)) => String::from("Running"),
+            Ok(Some(false)) => String::from("Not Running"),
+            Ok(None) => String::from("Missing"),
+            Err(_) => String::from("Parsing error"),
         }
-        let seconds_since_last_result = self.get_seconds_since_last();
-        if seconds_since_last_result.is_err() {
-            return String::from("Parsing error");
-        };
-        let seconds_since = seconds_since_last_result.unwrap();
-        match seconds_since {
-            seconds_since if seconds_since <= 60 * 10 => String::from("Running"),
-            seconds_since if seconds_since > 60 * 10 => String::from("Not Running"),
-            _ => String::from("Matching error"),
+    }
+
+    // Fans run while their VSD reports running, and are no longer known to be
+    // running once they went offline. None when the VSD flag isn't reported.
+    pub fn is_running(&self, policy: &StalenessPolicy) -> Result<Option<bool>, chrono::ParseError> {
+        match self.vsd_running {
+            Some(true) => match self.get_staleness(policy)? {
+                Staleness::Offline => Ok(Some(false)),
+                _ => Ok(Some(true)),
+            },
+            Some(false) => Ok(Some(false)),
+            None => Ok(None),
         }
     }
 
-    fn get_seconds_since_last(&self) -> Result<i64, chrono::ParseError> {
-        let datetime = &self.last_update;
-        let parsed_datetime =
-            chrono::NaiveDateTime::parse_from_str(&datetime, "%Y-%m-%d %H:%M:%S")?.timestamp();
-        let now = chrono::offset::Local::now().timestamp();
-        let difference_seconds = now - parsed_datetime;
-        Ok(difference_seconds)
+    pub fn get_staleness(&self, policy: &StalenessPolicy) -> Result<Staleness, chrono::ParseError> {
+        Ok(policy.get_staleness(self.get_seconds_since_last()?))
+    }
+
+    pub fn get_last_update_datetime(&self) -> Result<chrono::NaiveDateTime, chrono::ParseError> {
+        chrono::NaiveDateTime::parse_from_str(&self.last_update, DATETIME_FORMAT)
+    }
+
+    // Readings are stamped in local time without an offset, so they are
+    // compared to the local wall clock rather than to a UTC timestamp
+    pub fn get_seconds_since_last(&self) -> Result<i64, chrono::ParseError> {
+        let parsed_datetime = self.get_last_update_datetime()?;
+        let now = chrono::offset::Local::now().naive_local();
+        Ok((now - parsed_datetime).num_seconds())
+    }
+}
+
+// Rounded to hundredths so 9.9 - 7.0 shows as 2.9 rather than 2.8999996
+fn get_temperature_difference(a: f32, b: f32) -> f32 {
+    ((a - b) * 100.0).round() / 100.0
+}
+
+// e.g. 5 Minutes Ago, readings stamped ahead of the local clock are Just Now
+fn format_time_since(seconds: i64) -> String {
+    match seconds {
+        seconds if seconds < 0 => String::from("Just Now"),
+        seconds if seconds >= 60 * 60 * 24 => format!("{} Days Ago", seconds / (60 * 60 * 24)),
+        seconds if seconds >= 60 * 60 => format!("{} Hours Ago", seconds / (60 * 60)),
+        seconds => format!("{} Minutes Ago", seconds / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_time_since_a_reading() {
+        assert_eq!(format_time_since(0), "0 Minutes Ago");
+        assert_eq!(format_time_since(59 * 60), "59 Minutes Ago");
+        assert_eq!(format_time_since(60 * 60 - 1), "59 Minutes Ago");
+        assert_eq!(format_time_since(60 * 60), "1 Hours Ago");
+        assert_eq!(format_time_since(60 * 60 * 24 - 1), "23 Hours Ago");
+        assert_eq!(format_time_since(60 * 60 * 24), "1 Days Ago");
+        assert_eq!(format_time_since(60 * 60 * 24 * 3 + 5), "3 Days Ago");
+    }
+
+    #[test]
+    fn readings_from_the_future_are_just_now() {
+        assert_eq!(format_time_since(-1), "Just Now");
+        assert_eq!(format_time_since(-60 * 60), "Just Now");
     }
 }
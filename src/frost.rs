@@ -0,0 +1,139 @@
+use crate::fan_data::FanData;
+
+// Bottom temperature the frost event detector uses when none is given
+pub const DEFAULT_FROST_THRESHOLD: f32 = 0.0;
+
+// Period where the bottom temperature stayed below the frost threshold
+#[derive(Clone, Debug)]
+pub struct FrostEvent {
+    pub start: String,
+    // None while the bottom temperature is still below the threshold
+    pub end: Option<String>,
+    pub min_temperature: f32,
+}
+
+// Finds frost events in a fan's history, which must be ordered oldest first.
+// Readings without a valid bottom temperature neither start nor end an event.
+pub fn detect_frost_events(history: &[FanData], threshold: f32) -> Vec<FrostEvent> {
+    let mut events = Vec::new();
+    let mut current: Option<FrostEvent> = None;
+    for fan_data in history {
+        let temperature = match fan_data.temperature_bottom.value() {
+            Some(temperature) => temperature,
+            None => continue,
+        };
+        match current.as_mut() {
+            Some(event) if temperature < threshold => {
+                event.min_temperature = event.min_temperature.min(temperature);
+            }
+            Some(event) => {
+                event.end = Some(fan_data.last_update.clone());
+                events.extend(current.take());
+            }
+            None if temperature < threshold => {
+                current = Some(FrostEvent {
+                    start: fan_data.last_update.clone(),
+                    end: None,
+                    min_temperature: temperature,
+                });
+            }
+            None => {}
+        }
+    }
+    events.extend(current);
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fan_data::Reading;
+
+    // Readings a minute apart from midnight with the given bottom temperatures
+    fn get_history(temperatures: &[Reading<f32>]) -> Vec<FanData> {
+        temperatures
+            .iter()
+            .enumerate()
+            .map(|(minute, temperature)| FanData {
+                temperature_bottom: *temperature,
+                ..FanData::new(format!("2024-05-01 00:{minute:02}:00"))
+            })
+            .collect()
+    }
+
+    fn get_spans(events: &[FrostEvent]) -> Vec<(&str, Option<&str>, f32)> {
+        events
+            .iter()
+            .map(|event| {
+                (
+                    event.start.as_str(),
+                    event.end.as_deref(),
+                    event.min_temperature,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn events_run_from_the_first_reading_below_to_the_first_one_back_above() {
+        let history = get_history(&[
+            Reading::Ok(1.0),
+            Reading::Ok(-0.5),
+            Reading::Ok(-1.5),
+            Reading::Ok(-1.0),
+            Reading::Ok(0.5),
+            Reading::Ok(2.0),
+        ]);
+        assert_eq!(
+            get_spans(&detect_frost_events(&history, 0.0)),
+            vec![("2024-05-01 00:01:00", Some("2024-05-01 00:04:00"), -1.5)]
+        );
+    }
+
+    #[test]
+    fn readings_at_the_threshold_are_not_frost() {
+        let history = get_history(&[Reading::Ok(0.0), Reading::Ok(-0.1), Reading::Ok(0.0)]);
+        assert_eq!(
+            get_spans(&detect_frost_events(&history, 0.0)),
+            vec![("2024-05-01 00:01:00", Some("2024-05-01 00:02:00"), -0.1)]
+        );
+        let history = get_history(&[Reading::Ok(0.0), Reading::Ok(0.0)]);
+        assert!(detect_frost_events(&history, 0.0).is_empty());
+    }
+
+    #[test]
+    fn events_still_below_at_the_end_stay_open() {
+        let history = get_history(&[
+            Reading::Ok(3.0),
+            Reading::Ok(1.5),
+            Reading::Ok(0.5),
+            Reading::Ok(2.0),
+            Reading::Ok(0.5),
+        ]);
+        assert_eq!(
+            get_spans(&detect_frost_events(&history, 1.0)),
+            vec![
+                ("2024-05-01 00:02:00", Some("2024-05-01 00:03:00"), 0.5),
+                ("2024-05-01 00:04:00", None, 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_readings_neither_start_nor_end_events() {
+        let history = get_history(&[
+            Reading::Error,
+            Reading::Ok(-1.0),
+            Reading::Missing,
+            Reading::Disabled,
+            Reading::Ok(-2.0),
+            Reading::Error,
+        ]);
+        assert_eq!(
+            get_spans(&detect_frost_events(&history, 0.0)),
+            vec![("2024-05-01 00:01:00", None, -2.0)]
+        );
+        let history = get_history(&[Reading::Missing, Reading::Disabled, Reading::Error]);
+        assert!(detect_frost_events(&history, 0.0).is_empty());
+    }
+}
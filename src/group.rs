@@ -0,0 +1,103 @@
+use crate::device::Layout;
+use crate::fan_data::{FanData, Reading};
+use crate::sqlite::Device;
+use crate::staleness::Staleness;
+use std::collections::HashMap;
+
+// Devices of one block or orchard, e.g. "North Cherries"
+pub struct Group {
+    pub name: String,
+    // Indexes into the devices, in tab order
+    pub devices: Vec<usize>,
+}
+
+// Aggregates shown for a group
+pub struct GroupSummary {
+    pub fans: usize,
+    pub running: usize,
+    // Fans that went offline or never reported
+    pub offline: usize,
+    // Lowest bottom temperature among the devices whose readings aren't stale,
+    // and the index of the device that measured it
+    pub min_temperature_bottom: Option<(f32, usize)>,
+}
+
+// Devices listed in the config file by name, serial number or MAC move to
+// that group, whatever the fan_groups table says
+pub fn apply_config_groups(devices: &mut [Device], groups: &HashMap<String, String>) {
+    for device in devices {
+        let group = [
+            Some(&device.name),
+            Some(&device.serial_number),
+            device.mac.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(|key| groups.get(key));
+        if let Some(group) = group {
+            device.group = Some(group.clone());
+        }
+    }
+}
+
+// Groups in the order of their first device, ungrouped devices are left out
+pub fn get_groups(devices: &[Device]) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    for (index, device) in devices.iter().enumerate() {
+        let name = match &device.group {
+            Some(name) => name,
+            None => continue,
+        };
+        match groups.iter_mut().find(|group| &group.name == name) {
+            Some(group) => group.devices.push(index),
+            None => groups.push(Group {
+                name: name.clone(),
+                devices: vec![index],
+            }),
+        }
+    }
+    groups
+}
+
+pub fn get_group_summary(
+    group: &Group,
+    devices: &[Device],
+    overview: &[Option<FanData>],
+) -> GroupSummary {
+    let mut summary = GroupSummary {
+        fans: 0,
+        running: 0,
+        offline: 0,
+        min_temperature_bottom: None,
+    };
+    for index in &group.devices {
+        let device = &devices[*index];
+        let fan_data = overview.get(*index).and_then(Option::as_ref);
+        let staleness = fan_data
+            .and_then(|fan_data| fan_data.get_staleness(&device.staleness).ok())
+            .unwrap_or(Staleness::Offline);
+        if device.device_type.layout == Layout::Fan {
+            summary.fans += 1;
+            match (fan_data, staleness) {
+                (_, Staleness::Offline) => summary.offline += 1,
+                (Some(fan_data), _) if fan_data.is_running(&device.staleness) == Ok(Some(true)) => {
+                    summary.running += 1
+                }
+                _ => {}
+            }
+        }
+        let temperature = match (fan_data, staleness) {
+            (Some(fan_data), Staleness::Online | Staleness::Late) => fan_data.temperature_bottom,
+            _ => Reading::Missing,
+        };
+        if let Reading::Ok(temperature) = temperature {
+            if summary
+                .min_temperature_bottom
+                .is_none_or(|(min, _)| temperature < min)
+            {
+                summary.min_temperature_bottom = Some((temperature, *index));
+            }
+        }
+    }
+    summary
+}
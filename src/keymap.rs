@@ -0,0 +1,494 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Help,
+    NextDevice,
+    PreviousDevice,
+    // Next overview row, or scrolls the detail view
+    Down,
+    Up,
+    OpenDetail,
+    Overview,
+    ToggleOverview,
+    Groups,
+    Watchdog,
+    History,
+    HistoryWindow,
+    Maintenance,
+    WindRose,
+    ExportCsv,
+    ExportJson,
+    Search,
+    // Jumps to the Nth device tab, counting from 1
+    Device(usize),
+}
+
+pub struct ActionInfo {
+    pub action: Action,
+    // Name in the [keymap] section of the config file
+    pub name: &'static str,
+    pub description: &'static str,
+    default_keys: &'static [&'static str],
+}
+
+// Every action in the order the help popup lists them
+pub static ACTIONS: [ActionInfo; 27] = [
+    ActionInfo {
+        action: Action::Quit,
+        name: "quit",
+        description: "Quit",
+        default_keys: &["q"],
+    },
+    ActionInfo {
+        action: Action::Help,
+        name: "help",
+        description: "Show or hide this help",
+        default_keys: &["?"],
+    },
+    ActionInfo {
+        action: Action::NextDevice,
+        name: "next_device",
+        description: "Next device",
+        default_keys: &["Right", "l"],
+    },
+    ActionInfo {
+        action: Action::PreviousDevice,
+        name: "previous_device",
+        description: "Previous device",
+        default_keys: &["Left", "h"],
+    },
+    ActionInfo {
+        action: Action::Down,
+        name: "down",
+        description: "Next overview row, scroll panels down",
+        default_keys: &["Down", "j"],
+    },
+    ActionInfo {
+        action: Action::Up,
+        name: "up",
+        description: "Previous overview row, scroll panels up",
+        default_keys: &["Up", "k"],
+    },
+    ActionInfo {
+        action: Action::OpenDetail,
+        name: "open_detail",
+        description: "Open the selected overview row",
+        default_keys: &["Enter"],
+    },
+    ActionInfo {
+        action: Action::Overview,
+        name: "overview",
+        description: "Open the overview",
+        default_keys: &["Esc"],
+    },
+    ActionInfo {
+        action: Action::ToggleOverview,
+        name: "toggle_overview",
+        description: "Switch between the overview and device details",
+        default_keys: &["o"],
+    },
+    ActionInfo {
+        action: Action::Groups,
+        name: "groups",
+        description: "Show or hide fan groups",
+        default_keys: &["g"],
+    },
+    ActionInfo {
+        action: Action::Watchdog,
+        name: "watchdog",
+        description: "Show or hide devices by time since their last report",
+        default_keys: &["W"],
+    },
+    ActionInfo {
+        action: Action::History,
+        name: "history",
+        description: "Show or hide history charts",
+        default_keys: &["c"],
+    },
+    ActionInfo {
+        action: Action::HistoryWindow,
+        name: "history_window",
+        description: "Change the history window",
+        default_keys: &["w"],
+    },
+    ActionInfo {
+        action: Action::Maintenance,
+        name: "maintenance",
+        description: "Show or hide a fan's maintenance report",
+        default_keys: &["m"],
+    },
+    ActionInfo {
+        action: Action::WindRose,
+        name: "wind_rose",
+        description: "Switch the compass to the wind rose",
+        default_keys: &["r"],
+    },
+    ActionInfo {
+        action: Action::ExportCsv,
+        name: "export_csv",
+        description: "Export the current view to CSV",
+        default_keys: &["e"],
+    },
+    ActionInfo {
+        action: Action::ExportJson,
+        name: "export_json",
+        description: "Export the current view to JSON",
+        default_keys: &["E"],
+    },
+    ActionInfo {
+        action: Action::Search,
+        name: "search",
+        description: "Search devices by name, serial number or MAC",
+        default_keys: &["/", "Ctrl-p"],
+    },
+    ActionInfo {
+        action: Action::Device(1),
+        name: "device_1",
+        description: "Jump to device 1",
+        default_keys: &["1"],
+    },
+    ActionInfo {
+        action: Action::Device(2),
+        name: "device_2",
+        description: "Jump to device 2",
+        default_keys: &["2"],
+    },
+    ActionInfo {
+        action: Action::Device(3),
+        name: "device_3",
+        description: "Jump to device 3",
+        default_keys: &["3"],
+    },
+    ActionInfo {
+        action: Action::Device(4),
+        name: "device_4",
+        description: "Jump to device 4",
+        default_keys: &["4"],
+    },
+    ActionInfo {
+        action: Action::Device(5),
+        name: "device_5",
+        description: "Jump to device 5",
+        default_keys: &["5"],
+    },
+    ActionInfo {
+        action: Action::Device(6),
+        name: "device_6",
+        description: "Jump to device 6",
+        default_keys: &["6"],
+    },
+    ActionInfo {
+        action: Action::Device(7),
+        name: "device_7",
+        description: "Jump to device 7",
+        default_keys: &["7"],
+    },
+    ActionInfo {
+        action: Action::Device(8),
+        name: "device_8",
+        description: "Jump to device 8",
+        default_keys: &["8"],
+    },
+    ActionInfo {
+        action: Action::Device(9),
+        name: "device_9",
+        description: "Jump to device 9",
+        default_keys: &["9"],
+    },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for Key {
+    // Shift is part of the character itself, e.g. 'E' or '?'
+    fn from(event: KeyEvent) -> Key {
+        let modifiers = match event.code {
+            KeyCode::Char(_) => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        Key {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Keymap {
+    // Keys of each action in the order they were given
+    bindings: Vec<(Key, Action)>,
+}
+
+impl Keymap {
+    pub fn get_action(&self, event: KeyEvent) -> Option<Action> {
+        let key = Key::from(event);
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, action)| *action)
+    }
+
+    // Display names of the keys bound to an action
+    pub fn get_keys(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(key, _)| format_key(key))
+            .collect()
+    }
+
+    // First key of an action for hints in titles, e.g. "[c]"
+    pub fn get_hint(&self, action: Action) -> String {
+        match self.get_keys(action).first() {
+            Some(key) => format!("[{key}]"),
+            None => String::from("[unbound]"),
+        }
+    }
+}
+
+// Actions listed in the config file replace all of their default keys, a key
+// taken by an action in the config file is removed from the other actions.
+// Quit has to keep at least one key.
+pub fn parse_keymap(overrides: &HashMap<String, Vec<String>>) -> Result<Keymap, String> {
+    let mut bindings = Vec::new();
+    for info in &ACTIONS {
+        for key in info.default_keys {
+            bindings.push((parse_key(key)?, info.action));
+        }
+    }
+    let mut bound: Vec<(Key, &str)> = Vec::new();
+    for (name, keys) in overrides {
+        let action = match ACTIONS.iter().find(|info| info.name == name) {
+            Some(info) => info.action,
+            None => return Err(format!("Unknown action \"{name}\" in keymap")),
+        };
+        bindings.retain(|(_, bound)| *bound != action);
+        for key in keys {
+            let parsed = parse_key(key).map_err(|error| format!("Keymap \"{name}\": {error}"))?;
+            if let Some((_, other)) = bound.iter().find(|(bound, _)| *bound == parsed) {
+                return Err(format!("Key \"{key}\" is bound to both {other} and {name}"));
+            }
+            bound.push((parsed, name));
+            bindings.retain(|(bound, _)| *bound != parsed);
+            bindings.push((parsed, action));
+        }
+    }
+    // Raw mode swallows Ctrl-C, quit is the only way out
+    if !bindings.iter().any(|(_, action)| *action == Action::Quit) {
+        return Err(String::from("Keymap leaves quit without a key"));
+    }
+    Ok(Keymap { bindings })
+}
+
+// Keys such as "q", "E", "?", "Left", "PageDown", "F1", "Ctrl-p" or "Alt-Enter"
+fn parse_key(key: &str) -> Result<Key, String> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = key;
+    loop {
+        let lowercase = name.to_lowercase();
+        if lowercase.starts_with("ctrl-") && name.len() > 5 {
+            modifiers |= KeyModifiers::CONTROL;
+            name = &name[5..];
+        } else if lowercase.starts_with("alt-") && name.len() > 4 {
+            modifiers |= KeyModifiers::ALT;
+            name = &name[4..];
+        } else {
+            break;
+        }
+    }
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(character), None) => KeyCode::Char(character),
+        _ => match name.to_lowercase().as_str() {
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            function => match function.strip_prefix('f').map(str::parse::<u8>) {
+                Some(Ok(number)) if (1..=12).contains(&number) => KeyCode::F(number),
+                _ => return Err(format!("unknown key \"{key}\"")),
+            },
+        },
+    };
+    Ok(Key { code, modifiers })
+}
+
+fn format_key(key: &Key) -> String {
+    let name = match key.code {
+        KeyCode::Char(' ') => String::from("Space"),
+        KeyCode::Char(character) => character.to_string(),
+        KeyCode::Left => String::from("←"),
+        KeyCode::Right => String::from("→"),
+        KeyCode::Up => String::from("↑"),
+        KeyCode::Down => String::from("↓"),
+        KeyCode::F(number) => format!("F{number}"),
+        code => format!("{:?}", code),
+    };
+    let mut prefix = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        prefix.push_str("Ctrl-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        prefix.push_str("Alt-");
+    }
+    prefix + &name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        Key { code, modifiers }
+    }
+
+    #[test]
+    fn parses_characters_names_and_function_keys() {
+        assert_eq!(
+            parse_key("q"),
+            Ok(key(KeyCode::Char('q'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("E"),
+            Ok(key(KeyCode::Char('E'), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("PageDown"),
+            Ok(key(KeyCode::PageDown, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("left"),
+            Ok(key(KeyCode::Left, KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("Space"),
+            Ok(key(KeyCode::Char(' '), KeyModifiers::NONE))
+        );
+        assert_eq!(
+            parse_key("F12"),
+            Ok(key(KeyCode::F(12), KeyModifiers::NONE))
+        );
+        assert!(parse_key("F13").is_err());
+        assert!(parse_key("F0").is_err());
+        assert!(parse_key("Fn").is_err());
+    }
+
+    #[test]
+    fn parses_modifiers() {
+        assert_eq!(
+            parse_key("Ctrl-p"),
+            Ok(key(KeyCode::Char('p'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("alt-Enter"),
+            Ok(key(KeyCode::Enter, KeyModifiers::ALT))
+        );
+        assert_eq!(
+            parse_key("Ctrl-Alt-x"),
+            Ok(key(
+                KeyCode::Char('x'),
+                KeyModifiers::CONTROL | KeyModifiers::ALT
+            ))
+        );
+        // A modifier without a key is the key itself
+        assert!(parse_key("Ctrl-").is_err());
+        assert_eq!(
+            parse_key("-"),
+            Ok(key(KeyCode::Char('-'), KeyModifiers::NONE))
+        );
+    }
+
+    #[test]
+    fn reports_unknown_keys() {
+        assert_eq!(
+            parse_key("Hyper"),
+            Err(String::from("unknown key \"Hyper\""))
+        );
+        assert!(parse_key("").is_err());
+    }
+
+    #[test]
+    fn formats_keys_for_hints() {
+        assert_eq!(format_key(&parse_key("Ctrl-p").unwrap()), "Ctrl-p");
+        assert_eq!(format_key(&parse_key("Down").unwrap()), "↓");
+        assert_eq!(format_key(&parse_key("Space").unwrap()), "Space");
+    }
+
+    #[test]
+    fn default_keymap_binds_every_action() {
+        let keymap = parse_keymap(&HashMap::new()).unwrap();
+        for info in &ACTIONS {
+            assert!(!keymap.get_keys(info.action).is_empty(), "{}", info.name);
+        }
+        assert_eq!(keymap.get_hint(Action::History), "[c]");
+        let event = KeyEvent::new(KeyCode::Char('E'), KeyModifiers::SHIFT);
+        assert_eq!(keymap.get_action(event), Some(Action::ExportJson));
+    }
+
+    #[test]
+    fn overrides_replace_default_keys_and_take_keys_from_other_actions() {
+        let overrides = HashMap::from([
+            (String::from("history"), vec![String::from("r")]),
+            (String::from("search"), vec![String::from("F1")]),
+        ]);
+        let keymap = parse_keymap(&overrides).unwrap();
+        assert_eq!(keymap.get_keys(Action::History), vec!["r"]);
+        assert_eq!(keymap.get_keys(Action::Search), vec!["F1"]);
+        assert_eq!(keymap.get_hint(Action::WindRose), "[unbound]");
+        let event = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::NONE);
+        assert_eq!(keymap.get_action(event), None);
+    }
+
+    #[test]
+    fn rejects_keymaps_leaving_quit_unbound() {
+        let overrides = HashMap::from([(String::from("history"), vec![String::from("q")])]);
+        assert_eq!(
+            parse_keymap(&overrides).err(),
+            Some(String::from("Keymap leaves quit without a key"))
+        );
+        let overrides = HashMap::from([(String::from("quit"), Vec::new())]);
+        assert!(parse_keymap(&overrides).is_err());
+        let overrides = HashMap::from([
+            (String::from("history"), vec![String::from("q")]),
+            (String::from("quit"), vec![String::from("Ctrl-c")]),
+        ]);
+        let keymap = parse_keymap(&overrides).unwrap();
+        assert_eq!(keymap.get_keys(Action::Quit), vec!["Ctrl-c"]);
+    }
+
+    #[test]
+    fn rejects_unknown_actions_bad_keys_and_duplicate_overrides() {
+        let unknown = HashMap::from([(String::from("jump"), vec![String::from("x")])]);
+        assert_eq!(
+            parse_keymap(&unknown).err(),
+            Some(String::from("Unknown action \"jump\" in keymap"))
+        );
+        let bad = HashMap::from([(String::from("quit"), vec![String::from("Hyper")])]);
+        assert_eq!(
+            parse_keymap(&bad).err(),
+            Some(String::from("Keymap \"quit\": unknown key \"Hyper\""))
+        );
+        let duplicate = HashMap::from([
+            (String::from("quit"), vec![String::from("x")]),
+            (String::from("help"), vec![String::from("x")]),
+        ]);
+        let error = parse_keymap(&duplicate).err().unwrap();
+        assert!(error.starts_with("Key \"x\" is bound to both"), "{error}");
+    }
+}
@@ -1,19 +1,48 @@
 use clap::Parser;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
-use std::{error::Error, io, process::exit};
+use std::{
+    env,
+    error::Error,
+    io,
+    process::exit,
+    time::{Duration, Instant},
+};
 use tui::{
     backend::{Backend, CrosstermBackend},
     Terminal,
 };
+mod alarms;
 mod app;
+mod cli;
+mod compass;
+mod config;
+mod device;
+mod export;
 mod fan_data;
+mod frost;
+mod group;
+mod keymap;
+mod maintenance;
+mod schema;
+mod search;
 mod sqlite;
+mod staleness;
+mod status;
 mod ui;
+mod wind;
+mod worker;
+
+use app::View;
+use export::ExportFormat;
+use keymap::Action;
 
 /// AGI Dashboard Terminal UI
 #[derive(Parser, Debug)]
@@ -22,14 +51,95 @@ struct Args {
     /// Path to database file
     #[clap(short, long, value_parser)]
     database: String,
+
+    /// Print a database compatibility report and exit
+    #[clap(long, action)]
+    check_db: bool,
+
+    /// Path to alarm rules file, built-in rules are used when omitted
+    #[clap(short, long, value_parser)]
+    alarms: Option<String>,
+
+    /// Path to theme, layout, group and staleness config file, defaults to
+    /// $XDG_CONFIG_HOME/agi-tui/config.toml when it exists
+    #[clap(long, value_parser)]
+    config: Option<String>,
+
+    /// Bottom temperature in ℃ below which a frost event is recorded
+    #[clap(long, value_parser, allow_hyphen_values = true, default_value_t = frost::DEFAULT_FROST_THRESHOLD)]
+    frost_threshold: f32,
+
+    /// Seconds between refreshes of time based values, e.g. offline alarms.
+    /// New readings are shown as soon as they are written
+    #[clap(short, long, value_parser = clap::value_parser!(u64).range(1..), default_value_t = 5)]
+    refresh_secs: u64,
+
+    /// Draw without colors, statuses are told apart by bold, dim and reversed text.
+    /// Also enabled by setting NO_COLOR
+    #[clap(long, action)]
+    monochrome: bool,
+
+    /// Run a command without the terminal UI
+    #[clap(subcommand)]
+    command: Option<cli::Command>,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     // get database path
     let args = Args::parse();
     let database = args.database;
+    let tick_rate = Duration::from_secs(args.refresh_secs);
+    // check the database schema before entering the alternate screen
+    let report = match sqlite::Database::new(&database).and_then(|db| db.check_schema()) {
+        Ok(report) => report,
+        Err(error) => {
+            println!("{error}");
+            exit(1);
+        }
+    };
+    if args.check_db {
+        println!("{report}");
+        exit(if report.is_compatible() { 0 } else { 1 });
+    }
+    if !report.is_compatible() {
+        println!("{report}");
+        exit(1);
+    }
+    let config = match config::load_config(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(error) => {
+            println!("{error}");
+            exit(1);
+        }
+    };
+    if let Some(command) = args.command {
+        let result = sqlite::Database::new(&database)
+            .map_err(|error| error.to_string())
+            .and_then(|db| cli::run(&db, command, &config));
+        if let Err(error) = result {
+            eprintln!("{error}");
+            exit(1);
+        }
+        return Ok(());
+    }
+    let alarm_rules = match alarms::load_alarm_rules(args.alarms.as_deref()) {
+        Ok(rules) => rules,
+        Err(error) => {
+            println!("{error}");
+            exit(1);
+        }
+    };
     // create app and run it
-    let app = match app::App::new(database) {
+    // https://no-color.org
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let monochrome = args.monochrome || no_color;
+    let app = match app::App::new(
+        database,
+        alarm_rules,
+        args.frost_threshold,
+        monochrome,
+        config,
+    ) {
         Ok(good_app) => good_app,
         Err(error) => {
             println!("{error}");
@@ -43,7 +153,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let res = run_app(&mut terminal, app);
+    let res = run_app(&mut terminal, app, tick_rate);
 
     // restore terminal
     disable_raw_mode()?;
@@ -61,17 +171,90 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: app::App) -> io::Result<()> {
+// How often results pushed by the database worker are picked up
+const RECEIVE_INTERVAL: Duration = Duration::from_millis(100);
+
+fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    mut app: app::App,
+    tick_rate: Duration,
+) -> io::Result<()> {
+    let mut last_tick = Instant::now();
     loop {
         terminal.draw(|f| ui::ui(f, &app))?;
 
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('q') => return Ok(()),
-                KeyCode::Right => app.next(),
-                KeyCode::Left => app.previous(),
+        // Wait for input until the next tick is due, checking back often
+        // for data from the worker
+        let timeout = tick_rate
+            .checked_sub(last_tick.elapsed())
+            .unwrap_or_else(|| Duration::from_secs(0))
+            .min(RECEIVE_INTERVAL);
+        if event::poll(timeout)? {
+            match event::read()? {
+                // The search palette takes every key while it's open
+                Event::Key(key) if app.search.is_some() => {
+                    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match (key.code, control) {
+                        (KeyCode::Esc, _) => app.close_search(),
+                        (KeyCode::Enter, _) => app.confirm_search(),
+                        (KeyCode::Down | KeyCode::Tab, _) | (KeyCode::Char('n'), true) => {
+                            app.move_search_selection(true)
+                        }
+                        (KeyCode::Up | KeyCode::BackTab, _) | (KeyCode::Char('p'), true) => {
+                            app.move_search_selection(false)
+                        }
+                        (KeyCode::Backspace, _) => app.search_backspace(),
+                        (KeyCode::Char(character), false) => app.search_input(character),
+                        _ => {}
+                    }
+                }
+                Event::Key(key) => {
+                    match (app.show_help, app.view, app.config.keymap.get_action(key)) {
+                        (_, _, Some(Action::Quit)) => return Ok(()),
+                        // Any other key closes the help popup
+                        (true, _, _) => app.toggle_help(),
+                        (_, _, Some(Action::Help)) => app.toggle_help(),
+                        (_, _, Some(Action::Down)) if app.is_overview_shown() => app.next_row(),
+                        (_, _, Some(Action::Up)) if app.is_overview_shown() => app.previous_row(),
+                        (_, _, Some(Action::OpenDetail)) if app.is_overview_shown() => {
+                            app.open_detail()
+                        }
+                        (_, View::Groups, Some(Action::NextDevice)) => app.next_group(),
+                        (_, View::Groups, Some(Action::PreviousDevice)) => app.previous_group(),
+                        (_, View::Detail, Some(Action::Down)) => app.scroll_down(),
+                        (_, View::Detail, Some(Action::Up)) => app.scroll_up(),
+                        (_, _, Some(Action::Overview)) => app.open_overview(),
+                        (_, _, Some(Action::ToggleOverview)) => app.toggle_overview(),
+                        (_, _, Some(Action::Groups)) => app.toggle_groups(),
+                        (_, _, Some(Action::Watchdog)) => app.toggle_watchdog(),
+                        (_, _, Some(Action::NextDevice)) => app.next(),
+                        (_, _, Some(Action::PreviousDevice)) => app.previous(),
+                        (_, _, Some(Action::Device(number))) => app.select(number - 1),
+                        (_, _, Some(Action::History)) => app.toggle_history(),
+                        (_, _, Some(Action::HistoryWindow)) => app.next_history_window(),
+                        (_, _, Some(Action::Maintenance)) => app.toggle_maintenance(),
+                        (_, _, Some(Action::WindRose)) => app.toggle_wind_rose(),
+                        (_, _, Some(Action::ExportCsv)) => app.export(ExportFormat::Csv),
+                        (_, _, Some(Action::ExportJson)) => app.export(ExportFormat::Json),
+                        (_, _, Some(Action::Search)) => app.open_search(),
+                        _ => {}
+                    }
+                }
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        app.on_click(mouse.column, mouse.row)
+                    }
+                    MouseEventKind::ScrollDown => app.on_scroll(mouse.column, mouse.row, true),
+                    MouseEventKind::ScrollUp => app.on_scroll(mouse.column, mouse.row, false),
+                    _ => {}
+                },
                 _ => {}
             }
         }
+        if last_tick.elapsed() >= tick_rate {
+            app.on_tick();
+            last_tick = Instant::now();
+        }
+        app.receive();
     }
 }
@@ -0,0 +1,335 @@
+use crate::fan_data::DATETIME_FORMAT;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+
+// Readings further apart than this are a gap in the data, e.g. the fan was
+// offline, and the time in between isn't counted as run time
+const MAX_SAMPLE_GAP_SECONDS: i64 = 60 * 10;
+
+// Day of the year a frost season starts on, January 1st counts calendar
+// years. Winter seasons that span the new year start e.g. on July 1st in the
+// Northern Hemisphere.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeasonStart {
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Default for SeasonStart {
+    fn default() -> SeasonStart {
+        SeasonStart { month: 1, day: 1 }
+    }
+}
+
+impl SeasonStart {
+    // Start of the season now is in, which began last year when its day
+    // hasn't come yet this year
+    pub fn get_start(&self, now: NaiveDateTime) -> NaiveDateTime {
+        let start = NaiveDate::from_ymd(now.year(), self.month, self.day).and_hms(0, 0, 0);
+        match start > now {
+            true => NaiveDate::from_ymd(now.year() - 1, self.month, self.day).and_hms(0, 0, 0),
+            false => start,
+        }
+    }
+}
+
+// Running samples of a fan read so far, refreshes only read the rows added
+// since the last one
+#[derive(Default)]
+pub struct RunningHistory {
+    // File the rows were read from, see Database::get_data_version
+    pub file_id: Option<(u64, u64)>,
+    pub last_row_id: i64,
+    // (datetime, vsd running) pairs, oldest first
+    pub samples: Vec<(String, bool)>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct RunStats {
+    pub run_seconds: i64,
+    // Observed changes from not running to running
+    pub starts: usize,
+    pub longest_run_seconds: i64,
+}
+
+impl RunStats {
+    pub fn get_run_hours_string(&self) -> String {
+        format_hours(self.run_seconds)
+    }
+
+    pub fn get_longest_run_string(&self) -> String {
+        format_hours(self.longest_run_seconds)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DayStats {
+    pub date: String,
+    #[serde(flatten)]
+    pub stats: RunStats,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MaintenanceReport {
+    pub today: RunStats,
+    pub season: RunStats,
+    pub lifetime: RunStats,
+    // First day of the season, e.g. 2026-07-01
+    pub season_start: String,
+    // Newest day first
+    pub days: Vec<DayStats>,
+}
+
+// Continuous period where the fan reported running
+struct Run {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    started: bool,
+}
+
+// Builds the report from (datetime, running) samples ordered oldest first,
+// with per-day stats for the last day_count days up to now
+pub fn get_maintenance_report(
+    samples: &[(String, bool)],
+    now: NaiveDateTime,
+    day_count: usize,
+    season_start: SeasonStart,
+) -> MaintenanceReport {
+    let runs = get_runs(samples);
+    let today = now.date().and_hms(0, 0, 0);
+    let season_start = season_start.get_start(now);
+    let days = (0..day_count as i64)
+        .map(|days_ago| {
+            let start = today - Duration::days(days_ago);
+            DayStats {
+                date: start.format("%Y-%m-%d").to_string(),
+                stats: get_run_stats(&runs, start, start + Duration::days(1)),
+            }
+        })
+        .collect();
+    MaintenanceReport {
+        today: get_run_stats(&runs, today, today + Duration::days(1)),
+        season: get_run_stats(&runs, season_start, NaiveDateTime::MAX),
+        lifetime: get_run_stats(&runs, NaiveDateTime::MIN, NaiveDateTime::MAX),
+        season_start: season_start.format("%Y-%m-%d").to_string(),
+        days,
+    }
+}
+
+fn get_runs(samples: &[(String, bool)]) -> Vec<Run> {
+    let mut runs = Vec::new();
+    let mut current: Option<Run> = None;
+    let mut previous: Option<NaiveDateTime> = None;
+    for (datetime, running) in samples {
+        let datetime = match NaiveDateTime::parse_from_str(datetime, DATETIME_FORMAT) {
+            Ok(datetime) => datetime,
+            Err(_) => continue,
+        };
+        let gap = match previous {
+            Some(previous) => (datetime - previous).num_seconds() > MAX_SAMPLE_GAP_SECONDS,
+            None => true,
+        };
+        if gap {
+            runs.extend(current.take());
+        }
+        match current.as_mut() {
+            Some(run) => {
+                // A run that stopped is counted until the reading that saw it stopped
+                run.end = datetime;
+                if !running {
+                    runs.extend(current.take());
+                }
+            }
+            None if *running => {
+                current = Some(Run {
+                    start: datetime,
+                    end: datetime,
+                    // Runs after a gap may have started any time during it
+                    started: !gap,
+                });
+            }
+            None => {}
+        }
+        previous = Some(datetime);
+    }
+    runs.extend(current);
+    runs
+}
+
+// Run time within [from, to), runs crossing the bounds are cut at them
+fn get_run_stats(runs: &[Run], from: NaiveDateTime, to: NaiveDateTime) -> RunStats {
+    let mut stats = RunStats::default();
+    for run in runs {
+        let start = run.start.max(from);
+        let end = run.end.min(to);
+        let starts_inside = run.start >= from && run.start < to;
+        if end <= start && !starts_inside {
+            continue;
+        }
+        let seconds = (end - start).num_seconds().max(0);
+        stats.run_seconds += seconds;
+        stats.longest_run_seconds = stats.longest_run_seconds.max(seconds);
+        if run.started && starts_inside {
+            stats.starts += 1;
+        }
+    }
+    stats
+}
+
+// e.g. 12.5h
+fn format_hours(seconds: i64) -> String {
+    format!("{:.1}h", seconds as f64 / 3600.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, DATETIME_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn seasons_start_on_the_last_start_day() {
+        let season_start = SeasonStart { month: 7, day: 1 };
+        assert_eq!(
+            season_start.get_start(datetime("2026-10-18 12:00:00")),
+            datetime("2026-07-01 00:00:00")
+        );
+        assert_eq!(
+            season_start.get_start(datetime("2026-03-01 12:00:00")),
+            datetime("2025-07-01 00:00:00")
+        );
+        assert_eq!(
+            SeasonStart::default().get_start(datetime("2026-03-01 12:00:00")),
+            datetime("2026-01-01 00:00:00")
+        );
+    }
+
+    #[test]
+    fn winter_seasons_span_the_new_year() {
+        let samples: Vec<(String, bool)> = [
+            ("2025-12-31 23:50:00", true),
+            ("2026-01-01 00:00:00", true),
+            ("2026-01-01 00:10:00", false),
+        ]
+        .iter()
+        .map(|(datetime, running)| (datetime.to_string(), *running))
+        .collect();
+        let now = datetime("2026-01-02 12:00:00");
+        let report = get_maintenance_report(&samples, now, 1, SeasonStart { month: 7, day: 1 });
+        assert_eq!(report.season.run_seconds, 20 * 60);
+        assert_eq!(report.season.starts, 0);
+        assert_eq!(report.season_start, "2025-07-01");
+        let report = get_maintenance_report(&samples, now, 1, SeasonStart::default());
+        assert_eq!(report.season.run_seconds, 10 * 60);
+    }
+    fn get_samples(samples: &[(&str, bool)]) -> Vec<(String, bool)> {
+        samples
+            .iter()
+            .map(|(datetime, running)| (datetime.to_string(), *running))
+            .collect()
+    }
+
+    fn get_lifetime(samples: &[(&str, bool)]) -> RunStats {
+        let now = datetime("2026-05-02 12:00:00");
+        get_maintenance_report(&get_samples(samples), now, 0, SeasonStart::default()).lifetime
+    }
+
+    #[test]
+    fn counts_starts_and_runs_until_the_reading_that_saw_them_stop() {
+        let stats = get_lifetime(&[
+            ("2026-05-01 00:00:00", false),
+            ("2026-05-01 00:05:00", true),
+            ("2026-05-01 00:10:00", true),
+            ("2026-05-01 00:15:00", false),
+            ("2026-05-01 00:20:00", true),
+            ("2026-05-01 00:25:00", false),
+        ]);
+        assert_eq!(stats.starts, 2);
+        assert_eq!(stats.run_seconds, 15 * 60);
+        assert_eq!(stats.longest_run_seconds, 10 * 60);
+    }
+
+    #[test]
+    fn runs_seen_from_the_first_reading_have_no_start() {
+        let stats = get_lifetime(&[
+            ("2026-05-01 00:00:00", true),
+            ("2026-05-01 00:05:00", false),
+        ]);
+        assert_eq!(stats.starts, 0);
+        assert_eq!(stats.run_seconds, 5 * 60);
+    }
+
+    #[test]
+    fn gaps_end_runs_without_counting_the_time_in_between() {
+        let stats = get_lifetime(&[
+            ("2026-05-01 00:00:00", false),
+            ("2026-05-01 00:05:00", true),
+            ("2026-05-01 00:15:00", true),
+            // 10m 01s later, the fan may have stopped and started again
+            ("2026-05-01 00:25:01", true),
+            ("2026-05-01 00:30:01", false),
+        ]);
+        assert_eq!(stats.starts, 1);
+        assert_eq!(stats.run_seconds, 15 * 60);
+        assert_eq!(stats.longest_run_seconds, 10 * 60);
+    }
+
+    #[test]
+    fn runs_still_going_count_until_the_last_reading() {
+        let stats = get_lifetime(&[
+            ("2026-05-01 00:00:00", false),
+            ("2026-05-01 00:05:00", true),
+            ("2026-05-01 00:10:00", true),
+            ("2026-05-01 00:12:00", true),
+        ]);
+        assert_eq!(stats.starts, 1);
+        assert_eq!(stats.run_seconds, 7 * 60);
+    }
+
+    #[test]
+    fn invalid_datetimes_are_skipped() {
+        let stats = get_lifetime(&[
+            ("2026-05-01 00:00:00", false),
+            ("not a date", false),
+            ("2026-05-01 00:05:00", true),
+            ("2026-05-01 00:10:00", false),
+        ]);
+        assert_eq!(stats.starts, 1);
+        assert_eq!(stats.run_seconds, 5 * 60);
+    }
+
+    #[test]
+    fn periods_cut_runs_at_their_bounds() {
+        let samples = get_samples(&[
+            // Last season
+            ("2025-06-30 23:00:00", false),
+            ("2025-06-30 23:05:00", true),
+            ("2025-06-30 23:10:00", true),
+            ("2025-06-30 23:15:00", true),
+            ("2025-06-30 23:20:00", true),
+            ("2025-06-30 23:25:00", true),
+            ("2025-06-30 23:30:00", true),
+            ("2025-06-30 23:35:00", false),
+            // Yesterday, running past midnight
+            ("2026-05-01 23:50:00", false),
+            ("2026-05-01 23:55:00", true),
+            ("2026-05-02 00:05:00", true),
+            ("2026-05-02 00:10:00", false),
+        ]);
+        let now = datetime("2026-05-02 12:00:00");
+        let report = get_maintenance_report(&samples, now, 2, SeasonStart { month: 7, day: 1 });
+        assert_eq!(report.lifetime.run_seconds, 45 * 60);
+        assert_eq!(report.lifetime.starts, 2);
+        assert_eq!(report.season.run_seconds, 15 * 60);
+        assert_eq!(report.season.starts, 1);
+        assert_eq!(report.today.run_seconds, 10 * 60);
+        assert_eq!(report.today.starts, 0);
+        assert_eq!(report.days[0].date, "2026-05-02");
+        assert_eq!(report.days[0].stats.run_seconds, 10 * 60);
+        assert_eq!(report.days[1].date, "2026-05-01");
+        assert_eq!(report.days[1].stats.run_seconds, 5 * 60);
+        assert_eq!(report.days[1].stats.starts, 1);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::device::DeviceType;
+use crate::sqlite::{get_table_columns, table_exists, Device, TableSource};
+use rusqlite::{Connection, Error};
+use std::fmt;
+
+pub struct ColumnSpec {
+    pub name: &'static str,
+    pub sql_type: &'static str,
+    // Optional columns are reported but the app works without them
+    pub required: bool,
+    pub description: &'static str,
+}
+
+const fn column(
+    name: &'static str,
+    sql_type: &'static str,
+    required: bool,
+    description: &'static str,
+) -> ColumnSpec {
+    ColumnSpec {
+        name,
+        sql_type,
+        required,
+        description,
+    }
+}
+
+pub const DEVICES_COLUMNS: [ColumnSpec; 3] = [
+    column("sn", "TEXT", true, "Serial Number"),
+    column("name", "TEXT", true, "Device Name"),
+    column("type", "INTEGER", true, "Device Type"),
+];
+
+pub const FAN_SN_MAC_COLUMNS: [ColumnSpec; 2] = [
+    column("sn", "TEXT", true, "Serial Number"),
+    column("mac", "TEXT", true, "MAC Address"),
+];
+
+pub const FAN_GROUPS_COLUMNS: [ColumnSpec; 2] = [
+    column("sn", "TEXT", true, "Serial Number"),
+    column("group_name", "TEXT", true, "Group Name"),
+];
+
+pub const FAN_TABLE_COLUMNS: [ColumnSpec; 20] = [
+    column("datetime", "TEXT", true, "Last Update"),
+    column("th", "REAL", false, "Temperature Top"),
+    column("tl", "REAL", false, "Temperature Bottom"),
+    column("tf", "REAL", false, "Temperature Far"),
+    column("rh", "REAL", false, "Humidity"),
+    column("ws", "REAL", false, "Wind Speed"),
+    column("wd", "INTEGER", false, "Wind Direction"),
+    column("bv", "REAL", false, "Battery Voltage"),
+    column("cmpd", "INTEGER", false, "C&M Panel Door"),
+    column("mpd", "INTEGER", false, "Main Panel Door"),
+    column("vs", "REAL", false, "Motor Vibration"),
+    column("om", "INTEGER", false, "Control Method"),
+    column("ip", "INTEGER", false, "Main State"),
+    column("ass", "REAL", false, "Rain Meter"),
+    column("ms", "INTEGER", false, "Main Switch"),
+    column("mc", "REAL", false, "Motor Current"),
+    column("ves", "INTEGER", false, "VSD Error"),
+    column("vcmd", "INTEGER", false, "C&M Command"),
+    column("vrs", "INTEGER", false, "VSD Running"),
+    column("rssi", "REAL", false, "Signal Strength"),
+];
+
+pub const WEATHER_TABLE_COLUMNS: [ColumnSpec; 8] = [
+    column("datetime", "TEXT", true, "Last Update"),
+    column("th", "REAL", false, "Air Temperature"),
+    column("rh", "REAL", false, "Humidity"),
+    column("ws", "REAL", false, "Wind Speed"),
+    column("wd", "INTEGER", false, "Wind Direction"),
+    column("ass", "REAL", false, "Rain Meter"),
+    column("bv", "REAL", false, "Battery Voltage"),
+    column("rssi", "REAL", false, "Signal Strength"),
+];
+
+pub const PROBE_TABLE_COLUMNS: [ColumnSpec; 6] = [
+    column("datetime", "TEXT", true, "Last Update"),
+    column("th", "REAL", false, "Temperature Top"),
+    column("tl", "REAL", false, "Temperature Bottom"),
+    column("tf", "REAL", false, "Temperature Far"),
+    column("bv", "REAL", false, "Battery Voltage"),
+    column("rssi", "REAL", false, "Signal Strength"),
+];
+
+pub enum ColumnStatus {
+    Ok,
+    Missing,
+    // Declared type differs from the expected one
+    TypeMismatch(String),
+}
+
+pub struct ColumnReport {
+    pub spec: &'static ColumnSpec,
+    pub status: ColumnStatus,
+}
+
+pub struct TableReport {
+    pub table: String,
+    pub required: bool,
+    pub exists: bool,
+    pub columns: Vec<ColumnReport>,
+}
+
+pub struct SchemaReport {
+    pub tables: Vec<TableReport>,
+    // Device name, the data table it was mapped to and how it was found
+    pub device_tables: Vec<(String, Option<String>, Option<TableSource>)>,
+}
+
+impl TableReport {
+    pub fn is_compatible(&self) -> bool {
+        if !self.exists {
+            return !self.required;
+        }
+        self.columns
+            .iter()
+            .all(|column| !column.spec.required || !matches!(column.status, ColumnStatus::Missing))
+    }
+}
+
+impl SchemaReport {
+    pub fn is_compatible(&self) -> bool {
+        self.tables.iter().all(|table| table.is_compatible())
+    }
+}
+
+fn check_table(
+    connection: &Connection,
+    table: &str,
+    required: bool,
+    specs: &'static [ColumnSpec],
+) -> Result<TableReport, Error> {
+    let exists = table_exists(connection, table)?;
+    let mut columns = Vec::new();
+    if exists {
+        let actual_columns = get_table_columns(connection, table)?;
+        for spec in specs {
+            let status = match actual_columns.iter().find(|(name, _)| name == spec.name) {
+                None => ColumnStatus::Missing,
+                Some((_, sql_type)) if sql_type.eq_ignore_ascii_case(spec.sql_type) => {
+                    ColumnStatus::Ok
+                }
+                Some((_, sql_type)) => ColumnStatus::TypeMismatch(sql_type.clone()),
+            };
+            columns.push(ColumnReport { spec, status });
+        }
+    }
+    Ok(TableReport {
+        table: table.to_string(),
+        required,
+        exists,
+        columns,
+    })
+}
+
+pub fn check_schema(
+    connection: &Connection,
+    data_tables: &[(String, &'static DeviceType)],
+    devices: &[Device],
+) -> Result<SchemaReport, Error> {
+    let mut tables = vec![
+        check_table(connection, "devices", true, &DEVICES_COLUMNS)?,
+        check_table(connection, "fan_sn_mac", false, &FAN_SN_MAC_COLUMNS)?,
+        check_table(connection, "fan_groups", false, &FAN_GROUPS_COLUMNS)?,
+    ];
+    // Data tables are checked for the columns of the type their name matches
+    for (table, device_type) in data_tables {
+        tables.push(check_table(connection, table, true, device_type.columns)?);
+    }
+    let device_tables = devices
+        .iter()
+        .map(|device| {
+            (
+                device.name.clone(),
+                device.table.clone(),
+                device.table_source,
+            )
+        })
+        .collect();
+    Ok(SchemaReport {
+        tables,
+        device_tables,
+    })
+}
+
+impl fmt::Display for SchemaReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Database Compatibility Report")?;
+        for table in &self.tables {
+            writeln!(f)?;
+            if !table.exists {
+                let note = if table.required {
+                    "MISSING (required)"
+                } else {
+                    "missing (optional)"
+                };
+                writeln!(f, "{}: {}", table.table, note)?;
+                continue;
+            }
+            let note = if table.is_compatible() {
+                "ok"
+            } else {
+                "INCOMPATIBLE"
+            };
+            writeln!(f, "{}: {}", table.table, note)?;
+            for column in &table.columns {
+                let status = match &column.status {
+                    ColumnStatus::Ok => String::from("ok"),
+                    ColumnStatus::Missing if column.spec.required => {
+                        String::from("MISSING (required)")
+                    }
+                    ColumnStatus::Missing => String::from("missing, using default"),
+                    ColumnStatus::TypeMismatch(sql_type) => {
+                        format!("declared {sql_type}, expected {}", column.spec.sql_type)
+                    }
+                };
+                writeln!(
+                    f,
+                    "  {:<10} {:<20} {}",
+                    column.spec.name, column.spec.description, status
+                )?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "Device Tables")?;
+        for (name, table, source) in &self.device_tables {
+            match (table, source) {
+                (Some(table), Some(TableSource::Mapped) | None) => {
+                    writeln!(f, "  {:<24} {}", name, table)?
+                }
+                (Some(table), Some(source)) => {
+                    writeln!(f, "  {:<24} {} ({})", name, table, source.label())?
+                }
+                (None, _) => writeln!(f, "  {:<24} NO TABLE FOUND", name)?,
+            }
+        }
+        writeln!(f)?;
+        let result = if self.is_compatible() {
+            "Compatible"
+        } else {
+            "Not compatible"
+        };
+        write!(f, "Result: {result}")
+    }
+}
@@ -0,0 +1,133 @@
+use crate::sqlite::Device;
+
+// Palette narrowing the devices down to those matching a query
+pub struct Search {
+    pub query: String,
+    // Indexes into the devices, best match first
+    pub matches: Vec<usize>,
+    // Position in matches of the highlighted device
+    pub selected: usize,
+}
+
+impl Search {
+    // Starts with every device, the selected one highlighted
+    pub fn new(devices: &[Device], index: usize) -> Search {
+        Search {
+            query: String::new(),
+            matches: (0..devices.len()).collect(),
+            selected: index.min(devices.len().saturating_sub(1)),
+        }
+    }
+
+    pub fn set_query(&mut self, devices: &[Device], query: String) {
+        self.matches = search_devices(devices, &query);
+        self.query = query;
+        self.selected = 0;
+    }
+
+    pub fn move_selection(&mut self, down: bool) {
+        let count = self.matches.len();
+        if count == 0 {
+            return;
+        }
+        self.selected = match down {
+            true => (self.selected + 1) % count,
+            false => (self.selected + count - 1) % count,
+        };
+    }
+
+    pub fn get_selected_device(&self) -> Option<usize> {
+        self.matches.get(self.selected).copied()
+    }
+}
+
+// Devices whose name, serial number or MAC fuzzy match the query, best first
+// and in tab order among equal scores
+pub fn search_devices(devices: &[Device], query: &str) -> Vec<usize> {
+    let mut scores: Vec<(usize, i64)> = devices
+        .iter()
+        .enumerate()
+        .filter_map(|(index, device)| {
+            [
+                Some(device.name.as_str()),
+                Some(device.serial_number.as_str()),
+                device.mac.as_deref(),
+            ]
+            .iter()
+            .flatten()
+            .filter_map(|text| get_fuzzy_score(query, text))
+            .max()
+            .map(|score| (index, score))
+        })
+        .collect();
+    scores.sort_by(|(a_index, a_score), (b_index, b_score)| {
+        b_score.cmp(a_score).then(a_index.cmp(b_index))
+    });
+    scores.into_iter().map(|(index, _)| index).collect()
+}
+
+// Score of a text containing the query's characters in order, ignoring case
+// and spaces, None when it doesn't. Runs of consecutive characters and
+// matches at the start of words score higher, so "nc2" ranks North Cherries 2
+// above Nectarines 12.
+pub fn get_fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for character in query.to_lowercase().chars() {
+        if character.is_whitespace() {
+            continue;
+        }
+        let found = position + text[position..].iter().position(|c| *c == character)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 4;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_characters_in_order_ignoring_case_and_spaces() {
+        assert!(get_fuzzy_score("NC 2", "north cherries 2").is_some());
+        assert!(get_fuzzy_score("rfl", "River Flats 1").is_some());
+        assert_eq!(get_fuzzy_score("cn", "North Cherries"), None);
+        assert_eq!(get_fuzzy_score("x", "North Cherries"), None);
+    }
+
+    #[test]
+    fn empty_query_matches_everything_equally() {
+        assert_eq!(get_fuzzy_score("", "North Cherries 1"), Some(0));
+        assert_eq!(get_fuzzy_score("  ", ""), Some(0));
+    }
+
+    #[test]
+    fn ranks_word_starts_above_letters_inside_words() {
+        let cherries = get_fuzzy_score("nc2", "North Cherries 2").unwrap();
+        let nectarines = get_fuzzy_score("nc2", "Nectarines 12").unwrap();
+        assert!(cherries > nectarines, "{cherries} <= {nectarines}");
+    }
+
+    #[test]
+    fn ranks_consecutive_characters_above_scattered_ones() {
+        let consecutive = get_fuzzy_score("ort", "north").unwrap();
+        let scattered = get_fuzzy_score("ort", "over the top").unwrap();
+        assert!(consecutive > scattered, "{consecutive} <= {scattered}");
+    }
+
+    #[test]
+    fn matches_serial_numbers_and_macs() {
+        assert!(get_fuzzy_score("0005", "SN0005").is_some());
+        assert!(get_fuzzy_score("aa:bb", "AA:BB:CC:DD:EE:FF").is_some());
+    }
+}
@@ -1,301 +1,754 @@
-use crate::fan_data::FanData;
-use rusqlite::{Connection, Error, OpenFlags, Result};
-#[derive(Clone, Debug)]
-pub struct Fan {
+use crate::device::{get_device_type, DeviceType, DEVICE_TYPES};
+use crate::fan_data::{FanData, Reading};
+use crate::maintenance::RunningHistory;
+use crate::schema::{check_schema, SchemaReport};
+use crate::staleness::StalenessPolicy;
+use rusqlite::{Connection, Error, OpenFlags, OptionalExtension, Result, Row};
+use serde::Serialize;
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Device {
     pub name: String,
     pub serial_number: String,
     pub mac: Option<String>,
+    #[serde(rename = "type")]
+    pub device_type: &'static DeviceType,
+    // Data table holding this device's readings, None when no table matches
+    pub table: Option<String>,
+    // How the table was found, tables guessed from the device order are
+    // shown with a warning
+    pub table_source: Option<TableSource>,
+    // Block or orchard the device belongs to, from fan_groups or the config file
+    pub group: Option<String>,
+    // When the device counts as late, stale or offline, from the config file
+    #[serde(skip)]
+    pub staleness: StalenessPolicy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableSource {
+    // fan_tables entry, or sn/mac column of the data table
+    Mapped,
+    // Nth fan of the devices table reads from FanN, as the dashboard always
+    // assumed, and the Nth weather station from WeatherN
+    DeviceOrder,
+    // Remaining devices take the remaining tables of their type in number order
+    TableOrder,
 }
-// #[derive(Clone, Debug)]
-// pub enum ControlMethods {
-//     Manual = 0,
-//     TemperatureControl = 1,
-//     Auto = 2,
-//     ManualOn = 3,
-//     ManualOff = 4,
-// }
-
-// #[derive(Clone, Debug)]
-// pub struct FanData {
-//     // Panel Switch & Door Status
-//     pub main_panel_open: bool,
-//     // control_door: bool,
-//     pub main_switch: bool,
-
-//     // Fan Status
-//     pub vsd_error: bool,
-//     pub vsd_running: bool,
-//     pub vsd_command: bool,
-
-//     // Temperature Data
-//     pub temperature_top: f32,
-//     pub temperature_bottom: f32,
-//     pub temperature_far: f32,
-
-//     // Fan Information
-//     pub signal_strength: f32,
-//     pub control_method: u8,
-//     // pub last_update: DateTime<FixedOffset>,
-//     pub last_update: String,
-
-//     // Fan Data
-//     pub motor_vibration: f32,
-//     pub motor_current: f32,
-//     // main_state: bool,
-
-//     // Environmental Data
-//     pub humidity: f32,
-//     pub wind_speed: f32,
-//     pub wind_direction: i16,
-// }
+
+impl TableSource {
+    pub fn label(&self) -> &str {
+        match self {
+            TableSource::Mapped => "mapped",
+            TableSource::DeviceOrder => "inferred from device order",
+            TableSource::TableOrder => "inferred from table order",
+        }
+    }
+}
+
+// File identity and PRAGMA data_version, see Database::get_data_version
+pub type DataVersion = (Option<(u64, u64)>, i64);
+
+// Rowid and datetime of the newest row of a table, see Database::get_last_row
+pub type LastRow = (i64, Option<String>);
+
+// Room for the latest/history/rowid statements of every device table
+const STATEMENT_CACHE_CAPACITY: usize = 128;
 
 pub struct Database {
     database_path: String,
+    // Kept open for the lifetime of the Database, usually owned by the worker thread
+    connection: RefCell<Connection>,
+    // Device and inode of the file the connection was opened on
+    file_id: Cell<Option<(u64, u64)>>,
 }
 
 impl Database {
     pub fn new(database_path: &str) -> Result<Self, rusqlite::Error> {
-        Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-        // conn.close();
-        let path = database_path.to_string();
+        let connection = open_connection(database_path)?;
         Ok(Self {
-            database_path: path,
+            database_path: database_path.to_string(),
+            connection: RefCell::new(connection),
+            file_id: Cell::new(get_file_id(database_path)),
         })
     }
 
-    fn get_connection(&self) -> Result<Connection, Error> {
-        let conn =
-            Connection::open_with_flags(&self.database_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-        Ok(conn)
+    // Reopens the database first when the file was replaced or rotated, a
+    // missing file keeps the old connection until a new one shows up
+    fn get_connection(&self) -> Result<Ref<'_, Connection>, Error> {
+        let file_id = get_file_id(&self.database_path);
+        if file_id.is_some() && file_id != self.file_id.get() {
+            *self.connection.borrow_mut() = open_connection(&self.database_path)?;
+            self.file_id.set(file_id);
+        }
+        Ok(self.connection.borrow())
     }
 
-    pub fn get_fans(&self) -> Result<Vec<Fan>, rusqlite::Error> {
+    // Changes whenever another connection commits, or the file was replaced
+    pub fn get_data_version(&self) -> Result<DataVersion, rusqlite::Error> {
         let connection = self.get_connection()?;
-        let mut stmt = connection.prepare("SELECT devices.sn,name,mac FROM devices LEFT JOIN fan_sn_mac ON devices.sn=fan_sn_mac.sn WHERE type=1")?;
-        let fans_iter = stmt.query_map([], |row| {
-            Ok(Fan {
-                serial_number: row.get("sn")?,
-                name: row.get("name")?,
-                mac: row.get("mac")?,
-            })
-        })?;
-        let mut fans: Vec<Fan> = Vec::new();
-        for fan in fans_iter {
-            match fan {
-                Ok(f) => fans.push(f),
-                Err(_error) => println!("Something wrong with result"),
-            }
-        }
-        Ok(fans)
+        let version = connection.query_row("PRAGMA data_version", [], |row| row.get(0))?;
+        Ok((self.file_id.get(), version))
+    }
+
+    // Highest rowid of a device table, grows with every new reading
+    pub fn get_last_row_id(&self, table: &str) -> Result<Option<i64>, rusqlite::Error> {
+        let connection = self.get_connection()?;
+        let sql = format!("SELECT MAX(rowid) FROM {}", quote_identifier(table));
+        let mut stmt = connection.prepare_cached(&sql)?;
+        stmt.query_row([], |row| row.get(0))
+    }
+
+    // Rowid and datetime of the newest row of a device table. New rows change
+    // the rowid, the latest row rewritten in place its datetime.
+    pub fn get_last_row(&self, table: &str) -> Result<Option<LastRow>, rusqlite::Error> {
+        let connection = self.get_connection()?;
+        let sql = format!(
+            "SELECT rowid, datetime FROM {} ORDER BY rowid DESC LIMIT 1",
+            quote_identifier(table)
+        );
+        let mut stmt = connection.prepare_cached(&sql)?;
+        stmt.query_row([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .optional()
+    }
+
+    pub fn get_devices(&self) -> Result<Vec<Device>, rusqlite::Error> {
+        let connection = self.get_connection()?;
+        read_devices(&connection)
+    }
+
+    pub fn check_schema(&self) -> Result<SchemaReport, rusqlite::Error> {
+        // Device mapping can only be resolved when the devices table is usable
+        let devices = self.get_devices().unwrap_or_default();
+        let connection = self.get_connection()?;
+        let data_tables = get_data_tables(&connection)?;
+        check_schema(&connection, &data_tables, &devices)
     }
 
-    pub fn get_last_fan_data(&self, fan_id: usize) -> Result<Option<FanData>, rusqlite::Error> {
+    // Latest reading of a data table, decoded by the reader of its device type
+    pub fn get_last_fan_data(
+        &self,
+        table: &str,
+        device_type: &DeviceType,
+    ) -> Result<Option<FanData>, rusqlite::Error> {
         let connection = self.get_connection()?;
-        let table_name = format!("Fan{}", fan_id);
         let sql = format!(
             "SELECT * FROM {} ORDER BY datetime DESC LIMIT 1",
-            table_name
+            quote_identifier(table)
         );
-        let mut stmt = connection.prepare(&sql)?;
+        let mut stmt = connection.prepare_cached(&sql)?;
         let has_data = stmt.exists([])?;
-        if has_data == false {
+        if !has_data {
             return Ok(None);
         }
-        // let col_names = stmt.column_names();
-        // println!("{col_names:?}");
-        let fan_data = stmt.query_row([], |row| {
-            Ok(FanData {
-                // last_update: parse_datetime(row.get("datetime")?)?,
-                temperature_top: row.get("th")?,
-                temperature_bottom: row.get("tl")?,
-                temperature_far: row.get("tf")?,
-                humidity: row.get("rh")?,
-                wind_speed: row.get("ws")?,
-                wind_direction: row.get("wd")?,
-                battery_voltage: row.get("bv")?,
-                control_door: row.get("cmpd")?,
-                main_panel_open: row.get("mpd")?,
-                motor_vibration: row.get("vs")?,
-                control_method: row.get("om")?,
-                // main_state: row.get("ip")?,
-                rain_meter: row.get("ass")?,
-                main_switch: row.get("ms")?,
-                motor_current: row.get("mc")?,
-                vsd_error: row.get("ves")?,
-                vsd_command: row.get("vcmd")?,
-                vsd_running: row.get("vrs")?,
-                signal_strength: row.get("rssi")?,
-                last_update: row.get("datetime")?,
-            })
-        })?;
+        let fan_data = stmt.query_row([], device_type.reader)?;
         Ok(Some(fan_data))
     }
+
+    pub fn get_fan_data_history(
+        &self,
+        table: &str,
+        device_type: &DeviceType,
+        since: &str,
+    ) -> Result<Vec<FanData>, rusqlite::Error> {
+        let connection = self.get_connection()?;
+        let sql = format!(
+            "SELECT * FROM {} WHERE datetime >= ?1 ORDER BY datetime ASC",
+            quote_identifier(table)
+        );
+        let mut stmt = connection.prepare_cached(&sql)?;
+        let history_iter = stmt.query_map([since], device_type.reader)?;
+        let mut history: Vec<FanData> = Vec::new();
+        for fan_data in history_iter {
+            history.push(fan_data?);
+        }
+        Ok(history)
+    }
+
+    // Appends the (datetime, vsd running) pairs written since the history was
+    // last updated, for run hour counting. Histories of a replaced file or a
+    // table that lost rows are read again from the start.
+    pub fn update_running_history(
+        &self,
+        table: &str,
+        history: &mut RunningHistory,
+    ) -> Result<(), rusqlite::Error> {
+        let (file_id, _) = self.get_data_version()?;
+        let last_row_id = self.get_last_row_id(table)?.unwrap_or(0);
+        if history.file_id != file_id || last_row_id < history.last_row_id {
+            *history = RunningHistory {
+                file_id,
+                ..RunningHistory::default()
+            };
+        }
+        let connection = self.get_connection()?;
+        let columns = get_table_columns(&connection, table)?;
+        // Fans without a vrs column are never seen running
+        let running = match columns.iter().any(|(name, _)| name == "vrs") {
+            true => "COALESCE(vrs, 0)",
+            false => "0",
+        };
+        let sql = format!(
+            "SELECT rowid, datetime, {} FROM {} WHERE rowid > ?1 ORDER BY rowid ASC",
+            running,
+            quote_identifier(table)
+        );
+        let mut stmt = connection.prepare_cached(&sql)?;
+        let rows = stmt.query_map([history.last_row_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get(1)?, row.get(2)?))
+        })?;
+        let read = history.samples.len();
+        for row in rows {
+            let (row_id, datetime, running) = row?;
+            history.last_row_id = row_id;
+            history.samples.push((datetime, running));
+        }
+        // Rows are written in time order, the sort only moves late writes
+        if history.samples[read.saturating_sub(1)..]
+            .windows(2)
+            .any(|pair| pair[0].0 > pair[1].0)
+        {
+            history.samples.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        Ok(())
+    }
+}
+
+fn open_connection(database_path: &str) -> Result<Connection, Error> {
+    let connection = Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    connection.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+    Ok(connection)
+}
+
+#[cfg(unix)]
+fn get_file_id(path: &str) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// Replaced files can't be told apart without inodes, keep the first connection
+#[cfg(not(unix))]
+fn get_file_id(path: &str) -> Option<(u64, u64)> {
+    fs::metadata(path).ok().map(|_| (0, 0))
+}
+
+// Devices of every registered type with the data table they read from
+fn read_devices(connection: &Connection) -> Result<Vec<Device>, Error> {
+    let data_tables = get_data_tables(connection)?;
+    let table_lookup = get_table_lookup(connection)?;
+    let group_lookup = get_group_lookup(connection)?;
+    // MAC addresses are optional
+    let sql = if table_exists(connection, "fan_sn_mac")? {
+        "SELECT devices.sn,name,type,mac FROM devices LEFT JOIN fan_sn_mac ON devices.sn=fan_sn_mac.sn"
+    } else {
+        "SELECT sn,name,type,NULL AS mac FROM devices"
+    };
+    let mut stmt = connection.prepare_cached(sql)?;
+    let devices_iter = stmt.query_map([], |row| {
+        // Types without a registered reader are skipped
+        let device_type = match get_device_type(row.get("type")?) {
+            Some(device_type) => device_type,
+            None => return Ok(None),
+        };
+        let serial_number: String = row.get("sn")?;
+        let mac: Option<String> = row.get("mac")?;
+        let mut table = table_lookup.get(&serial_number).cloned();
+        if table.is_none() {
+            if let Some(mac) = &mac {
+                table = table_lookup.get(mac).cloned();
+            }
+        }
+        // Tables of another type's pattern would be read with the wrong reader
+        let table = table.filter(|table| {
+            data_tables
+                .iter()
+                .any(|(name, table_type)| name == table && table_type.id == device_type.id)
+        });
+        let table_source = table.as_ref().map(|_| TableSource::Mapped);
+        let group = group_lookup.get(&serial_number).cloned();
+        Ok(Some(Device {
+            serial_number,
+            name: row.get("name")?,
+            mac,
+            device_type,
+            table,
+            table_source,
+            group,
+            staleness: StalenessPolicy::default(),
+        }))
+    })?;
+    let mut devices: Vec<Device> = Vec::new();
+    for device in devices_iter {
+        if let Some(device) = device? {
+            devices.push(device);
+        }
+    }
+    infer_tables(&data_tables, &mut devices);
+    Ok(devices)
+}
+
+// Maps serial numbers and MAC addresses to the data table holding their
+// readings. Entries of the optional fan_tables(sn, table_name) mapping table
+// win over sn/mac columns found in the data tables themselves.
+fn get_table_lookup(connection: &Connection) -> Result<HashMap<String, String>, Error> {
+    let mut lookup = HashMap::new();
+
+    for (table, _) in get_data_tables(connection)? {
+        let columns = get_table_columns(connection, &table)?;
+        for column in ["sn", "mac"] {
+            if !columns.iter().any(|(name, _)| name == column) {
+                continue;
+            }
+            let sql = format!(
+                "SELECT {} FROM {} WHERE {} IS NOT NULL ORDER BY datetime DESC LIMIT 1",
+                column,
+                quote_identifier(&table),
+                column
+            );
+            let value: Option<String> = connection
+                .query_row(&sql, [], |row| row.get(0))
+                .optional()?
+                .flatten();
+            if let Some(value) = value {
+                lookup.insert(value, table.clone());
+            }
+        }
+    }
+
+    if table_exists(connection, "fan_tables")? {
+        let mut stmt = connection.prepare("SELECT sn,table_name FROM fan_tables")?;
+        let mapping_iter = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>("sn")?,
+                row.get::<_, String>("table_name")?,
+            ))
+        })?;
+        for mapping in mapping_iter {
+            let (serial_number, table) = mapping?;
+            lookup.insert(serial_number, table);
+        }
+    }
+    Ok(lookup)
+}
+
+// Databases without a mapping store the readings of the Nth fan of the
+// devices table in FanN, and this app expects the other types the same way,
+// e.g. WeatherN. Devices the lookup didn't find a table for get that one when
+// it exists and isn't taken, the rest take the unclaimed tables of their type
+// in number order.
+fn infer_tables(data_tables: &[(String, &'static DeviceType)], devices: &mut [Device]) {
+    let mut taken: HashSet<String> = devices
+        .iter()
+        .filter_map(|device| device.table.clone())
+        .collect();
+    for device_type in &DEVICE_TYPES {
+        let mut tables: Vec<(u32, &String)> = data_tables
+            .iter()
+            .filter(|(_, table_type)| table_type.id == device_type.id)
+            .filter_map(|(table, _)| Some((device_type.get_table_number(table)?, table)))
+            .collect();
+        tables.sort();
+        let indexes: Vec<usize> = (0..devices.len())
+            .filter(|index| devices[*index].device_type.id == device_type.id)
+            .collect();
+        for (position, index) in indexes.iter().enumerate() {
+            let table = format!("{}{}", device_type.table_prefix, position + 1);
+            let exists = tables.iter().any(|(_, name)| **name == table);
+            if devices[*index].table.is_none() && exists && !taken.contains(&table) {
+                taken.insert(table.clone());
+                devices[*index].table = Some(table);
+                devices[*index].table_source = Some(TableSource::DeviceOrder);
+            }
+        }
+        let mut remaining = tables
+            .into_iter()
+            .map(|(_, table)| table)
+            .filter(|table| !taken.contains(*table));
+        for index in indexes {
+            if devices[index].table.is_some() {
+                continue;
+            }
+            match remaining.next() {
+                Some(table) => {
+                    devices[index].table = Some(table.clone());
+                    devices[index].table_source = Some(TableSource::TableOrder);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+// Maps serial numbers to the group named in the optional
+// fan_groups(sn, group_name) table
+fn get_group_lookup(connection: &Connection) -> Result<HashMap<String, String>, Error> {
+    let mut lookup = HashMap::new();
+    if table_exists(connection, "fan_groups")? {
+        let mut stmt = connection.prepare("SELECT sn,group_name FROM fan_groups")?;
+        let mapping_iter = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>("sn")?,
+                row.get::<_, String>("group_name")?,
+            ))
+        })?;
+        for mapping in mapping_iter {
+            let (serial_number, group) = mapping?;
+            lookup.insert(serial_number, group);
+        }
+    }
+    Ok(lookup)
+}
+
+// Data tables of every registered device type and the type whose pattern
+// they match, e.g. Fan1 or Weather1
+fn get_data_tables(connection: &Connection) -> Result<Vec<(String, &'static DeviceType)>, Error> {
+    let mut stmt =
+        connection.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name GLOB ?1")?;
+    let mut tables = Vec::new();
+    for device_type in &DEVICE_TYPES {
+        let names = stmt
+            .query_map([device_type.get_table_pattern()], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<Result<Vec<String>, Error>>()?;
+        tables.extend(names.into_iter().map(|name| (name, device_type)));
+    }
+    Ok(tables)
+}
+
+pub fn table_exists(connection: &Connection, table: &str) -> Result<bool, Error> {
+    let mut stmt =
+        connection.prepare("SELECT 1 FROM sqlite_master WHERE type='table' AND name=?1")?;
+    stmt.exists([table])
+}
+
+// Name and declared type of every column of a table
+pub fn get_table_columns(
+    connection: &Connection,
+    table: &str,
+) -> Result<Vec<(String, String)>, Error> {
+    let sql = format!("PRAGMA table_info({})", quote_identifier(table));
+    let mut stmt = connection.prepare(&sql)?;
+    let columns = stmt
+        .query_map([], |row| Ok((row.get("name")?, row.get("type")?)))?
+        .collect::<Result<Vec<(String, String)>, Error>>()?;
+    Ok(columns)
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+// Readers decode the columns of their device type, every other value of the
+// reading stays missing
+
+pub fn fan_data_from_row(row: &Row) -> Result<FanData, rusqlite::Error> {
+    Ok(FanData {
+        // last_update: parse_datetime(row.get("datetime")?)?,
+        temperature_top: get_reading(row, "th")?,
+        temperature_bottom: get_reading(row, "tl")?,
+        temperature_far: get_reading(row, "tf")?,
+        humidity: get_reading(row, "rh")?,
+        wind_speed: get_reading(row, "ws")?,
+        wind_direction: get_reading(row, "wd")?.map(|value| value as i16),
+        battery_voltage: get_reading(row, "bv")?,
+        control_door: get_flag(row, "cmpd")?,
+        main_panel_open: get_flag(row, "mpd")?,
+        motor_vibration: get_reading(row, "vs")?,
+        control_method: get_control_method(row)?,
+        // main_state: row.get("ip")?,
+        rain_meter: get_reading(row, "ass")?,
+        main_switch: get_flag(row, "ms")?,
+        motor_current: get_reading(row, "mc")?,
+        vsd_error: get_flag(row, "ves")?,
+        vsd_command: get_flag(row, "vcmd")?,
+        vsd_running: get_flag(row, "vrs")?,
+        signal_strength: get_reading(row, "rssi")?,
+        last_update: row.get("datetime")?,
+    })
+}
+
+// Weather stations report their air temperature in th
+pub fn weather_data_from_row(row: &Row) -> Result<FanData, rusqlite::Error> {
+    Ok(FanData {
+        temperature_top: get_reading(row, "th")?,
+        humidity: get_reading(row, "rh")?,
+        wind_speed: get_reading(row, "ws")?,
+        wind_direction: get_reading(row, "wd")?.map(|value| value as i16),
+        rain_meter: get_reading(row, "ass")?,
+        battery_voltage: get_reading(row, "bv")?,
+        signal_strength: get_reading(row, "rssi")?,
+        ..FanData::new(row.get("datetime")?)
+    })
+}
+
+pub fn probe_data_from_row(row: &Row) -> Result<FanData, rusqlite::Error> {
+    Ok(FanData {
+        temperature_top: get_reading(row, "th")?,
+        temperature_bottom: get_reading(row, "tl")?,
+        temperature_far: get_reading(row, "tf")?,
+        battery_voltage: get_reading(row, "bv")?,
+        signal_strength: get_reading(row, "rssi")?,
+        ..FanData::new(row.get("datetime")?)
+    })
+}
+
+// Optional columns may be absent from older FanN tables
+fn has_column(row: &Row, column: &str) -> bool {
+    row.as_ref().column_index(column).is_ok()
+}
+
+// Decodes a sensor column once so callers never see the raw sentinels
+fn get_reading(row: &Row, column: &str) -> Result<Reading<f32>, rusqlite::Error> {
+    if !has_column(row, column) {
+        return Ok(Reading::Missing);
+    }
+    let raw: Option<f64> = row.get(column)?;
+    Ok(Reading::from_raw(raw).map(|value| value as f32))
+}
+
+// Missing columns and NULL flags are None rather than a made up state
+fn get_flag(row: &Row, column: &str) -> Result<Option<bool>, rusqlite::Error> {
+    if !has_column(row, column) {
+        return Ok(None);
+    }
+    row.get(column)
+}
+
+// Missing control methods are shown as Unknown
+fn get_control_method(row: &Row) -> Result<u8, rusqlite::Error> {
+    if !has_column(row, "om") {
+        return Ok(u8::MAX);
+    }
+    let value: Option<u8> = row.get("om")?;
+    Ok(value.unwrap_or(u8::MAX))
 }
 
-impl Fan {
+impl Device {
     pub fn get_name(&self) -> &str {
-        let name = &self.name;
-        name
+        &self.name
+    }
+
+    // Tables guessed from the order of devices or tables may belong to another device
+    pub fn get_table_warning(&self) -> Option<String> {
+        match (&self.table, self.table_source) {
+            (Some(table), Some(source)) if source != TableSource::Mapped => Some(format!(
+                "Table {} of {} is {}, add it to fan_tables to be sure",
+                table,
+                self.name,
+                source.label()
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn get_table_error(&self) -> String {
+        let mac = self.mac.as_deref().unwrap_or("no MAC");
+        format!(
+            "No data table found for {} ({}, {})",
+            self.name, self.serial_number, mac
+        )
     }
 }
 
-// impl FanData {
-//     pub fn get_temperature_top_string(&self) -> String {
-//         let value = self.temperature_top;
-//         match value {
-//             -49.0 => String::from("Disabled"),
-//             -50.0 => String::from("Error"),
-//             _ => format!("{}℃", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_temperature_bottom_string(&self) -> String {
-//         let value = self.temperature_bottom;
-//         match value {
-//             -49.0 => String::from("Disabled"),
-//             -50.0 => String::from("Error"),
-//             _ => format!("{}℃", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_temperature_far_string(&self) -> String {
-//         let value = self.temperature_far;
-//         match value {
-//             -49.0 => String::from("Disabled"),
-//             -50.0 => String::from("Error"),
-//             _ => format!("{}℃", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_motor_current_string(&self) -> String {
-//         let value = self.motor_current;
-//         match value {
-//             -49.0 => String::from("Disabled"),
-//             -50.0 => String::from("Error"),
-//             _ => format!("{}A", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_motor_vibration_string(&self) -> String {
-//         let value = self.motor_vibration;
-//         match value {
-//             -49.0 => String::from("Disabled"),
-//             -50.0 => String::from("Error"),
-//             _ => format!("{}mm/s", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_ready_string(&self) -> String {
-//         let value = self.vsd_error;
-//         match value {
-//             true => String::from("Error"),
-//             false => String::from("Ready"),
-//         }
-//     }
-
-//     pub fn get_running_string(&self) -> String {
-//         let value = self.vsd_running;
-//         match value {
-//             true => String::from("Running"),
-//             false => String::from("Idle"),
-//         }
-//     }
-
-//     pub fn get_command_string(&self) -> String {
-//         let value = self.vsd_command;
-//         match value {
-//             true => String::from("On"),
-//             false => String::from("Off"),
-//         }
-//     }
-
-//     pub fn get_operating_mode_string(&self) -> String {
-//         let value = self.control_method;
-//         match value {
-//             0 => String::from("Manual"),
-//             1 => String::from("Temperature Control"),
-//             2 => String::from("C&M Auto"),
-//             3 => String::from("C&M Manual On"),
-//             4 => String::from("C&M Manual Off"),
-//             _ => String::from("Unknown"),
-//         }
-//     }
-
-//     pub fn get_humidity_string(&self) -> String {
-//         let value = self.humidity;
-//         match value {
-//             -49.0 => String::from("Disabled"),
-//             -50.0 => String::from("Error"),
-//             _ => format!("{}%", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_wind_speed_string(&self) -> String {
-//         let value = self.wind_speed;
-//         match value {
-//             -49.0 => String::from("Disabled"),
-//             -50.0 => String::from("Error"),
-//             _ => format!("{}m/s", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_wind_direction_string(&self) -> String {
-//         let value = self.wind_direction;
-//         match value {
-//             -49 => String::from("Disabled"),
-//             -50 => String::from("Error"),
-//             _ => format!("{}°", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_signal_strength_string(&self) -> String {
-//         let value = self.signal_strength;
-//         match value {
-//             -49.0 => String::from("Disabled"),
-//             -50.0 => String::from("Error"),
-//             _ => format!("{}dBm", value.to_string()),
-//         }
-//     }
-
-//     pub fn get_main_panel_string(&self) -> String {
-//         let value = self.main_panel_open;
-//         match value {
-//             true => String::from("Closed"),
-//             false => String::from("Open"),
-//         }
-//     }
-
-//     pub fn get_main_switch_string(&self) -> String {
-//         let value = self.main_switch;
-//         match value {
-//             true => String::from("On"),
-//             false => String::from("Off"),
-//         }
-//     }
-
-//     pub fn get_last_update_string(&self) -> String {
-//         let datetime = &self.last_update;
-//         let parsed_datetime_result =
-//             chrono::NaiveDateTime::parse_from_str(&datetime, "%Y-%m-%d %H:%M:%S");
-//         if parsed_datetime_result.is_err() {
-//             return String::from("Parsing error");
-//         }
-//         let now = chrono::offset::Local::now().timestamp();
-//         let parsed_datetime = parsed_datetime_result.unwrap().timestamp();
-//         if now < parsed_datetime {
-//             return String::from("Error: last update later than now");
-//         }
-//         let difference_seconds = now - parsed_datetime;
-//         match difference_seconds {
-//             seconds if seconds > 60 * 60 => {
-//                 let hours = difference_seconds / (60 * 60);
-//                 format!("{hours} Hours Ago")
-//             }
-//             seconds if seconds < 60 * 60 => {
-//                 let minutes = difference_seconds / (60);
-//                 format!("{minutes} Minutes Ago")
-//             }
-//             _ => String::from("Matching error"),
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // In-memory database with the given devices and empty data tables
+    fn open_database(devices: &[(&str, &str, i64)], tables: &[&str]) -> Connection {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch("CREATE TABLE devices(sn TEXT, name TEXT, type INTEGER)")
+            .unwrap();
+        for (serial_number, name, device_type) in devices {
+            connection
+                .execute(
+                    "INSERT INTO devices VALUES (?1, ?2, ?3)",
+                    rusqlite::params![serial_number, name, device_type],
+                )
+                .unwrap();
+        }
+        for table in tables {
+            connection
+                .execute_batch(&format!("CREATE TABLE {table}(datetime TEXT)"))
+                .unwrap();
+        }
+        connection
+    }
+
+    fn get_tables(connection: &Connection) -> Vec<(String, Option<String>, Option<TableSource>)> {
+        read_devices(connection)
+            .unwrap()
+            .into_iter()
+            .map(|device| (device.name, device.table, device.table_source))
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_tables_of_each_device_type_in_device_order() {
+        let connection = open_database(
+            &[
+                ("SN0001", "Fan A", 1),
+                ("WS0001", "Station", 2),
+                ("SN0002", "Fan B", 1),
+                ("TP0001", "Probe", 3),
+                ("XX0001", "Unknown", 9),
+            ],
+            &["Fan1", "Fan2", "Weather1", "Probe1"],
+        );
+        let inferred = Some(TableSource::DeviceOrder);
+        assert_eq!(
+            get_tables(&connection),
+            vec![
+                (String::from("Fan A"), Some(String::from("Fan1")), inferred),
+                (
+                    String::from("Station"),
+                    Some(String::from("Weather1")),
+                    inferred
+                ),
+                (String::from("Fan B"), Some(String::from("Fan2")), inferred),
+                (
+                    String::from("Probe"),
+                    Some(String::from("Probe1")),
+                    inferred
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_devices_only_to_tables_of_their_type() {
+        let connection = open_database(&[("WS0001", "Station", 2)], &["Fan1", "Weather3"]);
+        connection
+            .execute_batch(
+                "CREATE TABLE fan_tables(sn TEXT, table_name TEXT);
+                 INSERT INTO fan_tables VALUES ('WS0001', 'Fan1');",
+            )
+            .unwrap();
+        assert_eq!(
+            get_tables(&connection),
+            vec![(
+                String::from("Station"),
+                Some(String::from("Weather3")),
+                Some(TableSource::TableOrder)
+            )]
+        );
+    }
+    #[test]
+    fn looks_tables_up_by_the_newest_serial_number_or_mac() {
+        let connection = open_database(&[], &[]);
+        connection
+            .execute_batch(
+                "CREATE TABLE Fan1(datetime TEXT, sn TEXT);
+                 INSERT INTO Fan1 VALUES ('2024-05-01 00:00:00', 'SN0009');
+                 INSERT INTO Fan1 VALUES ('2024-05-01 00:01:00', 'SN0001');
+                 INSERT INTO Fan1 VALUES ('2024-05-01 00:02:00', NULL);
+                 CREATE TABLE Fan2(datetime TEXT, mac TEXT);
+                 INSERT INTO Fan2 VALUES ('2024-05-01 00:00:00', 'AA:BB:CC:00:00:02');
+                 CREATE TABLE Fan3(datetime TEXT);",
+            )
+            .unwrap();
+        let lookup = get_table_lookup(&connection).unwrap();
+        assert_eq!(
+            lookup,
+            HashMap::from([
+                (String::from("SN0001"), String::from("Fan1")),
+                (String::from("AA:BB:CC:00:00:02"), String::from("Fan2")),
+            ])
+        );
+    }
+
+    #[test]
+    fn fan_tables_win_over_serial_number_columns() {
+        let connection = open_database(&[], &[]);
+        connection
+            .execute_batch(
+                "CREATE TABLE Fan1(datetime TEXT, sn TEXT);
+                 INSERT INTO Fan1 VALUES ('2024-05-01 00:00:00', 'SN0001');
+                 CREATE TABLE fan_tables(sn TEXT, table_name TEXT);
+                 INSERT INTO fan_tables VALUES ('SN0001', 'Fan7');",
+            )
+            .unwrap();
+        let lookup = get_table_lookup(&connection).unwrap();
+        assert_eq!(lookup["SN0001"], "Fan7");
+    }
+
+    #[test]
+    fn infers_fan_tables_around_mapped_ones() {
+        let connection = open_database(
+            &[
+                ("SN0001", "North 1", 1),
+                ("SN0002", "North 2", 1),
+                ("SN0003", "Decommissioned", 1),
+                ("SN0004", "River 1", 1),
+            ],
+            &["Fan1", "Fan2", "Fan4", "Fan10"],
+        );
+        // North 2 reads Fan1, so North 1 can't take it by its position
+        connection
+            .execute_batch(
+                "CREATE TABLE fan_tables(sn TEXT, table_name TEXT);
+                 INSERT INTO fan_tables VALUES ('SN0002', 'Fan1');",
+            )
+            .unwrap();
+        let fan = |name: &str, table: &str, source| {
+            (String::from(name), Some(String::from(table)), Some(source))
+        };
+        assert_eq!(
+            get_tables(&connection),
+            vec![
+                fan("North 1", "Fan2", TableSource::TableOrder),
+                fan("North 2", "Fan1", TableSource::Mapped),
+                fan("Decommissioned", "Fan10", TableSource::TableOrder),
+                fan("River 1", "Fan4", TableSource::DeviceOrder),
+            ]
+        );
+    }
+
+    #[test]
+    fn fans_left_without_a_table_get_none() {
+        let connection = open_database(
+            &[("SN0001", "North 1", 1), ("SN0002", "North 2", 1)],
+            &["Fan2"],
+        );
+        assert_eq!(
+            get_tables(&connection),
+            vec![
+                (String::from("North 1"), None, None),
+                (
+                    String::from("North 2"),
+                    Some(String::from("Fan2")),
+                    Some(TableSource::DeviceOrder)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_about_inferred_tables_only() {
+        let connection = open_database(
+            &[("SN0001", "North 1", 1), ("SN0002", "North 2", 1)],
+            &["Fan1", "Fan2"],
+        );
+        connection
+            .execute_batch(
+                "CREATE TABLE fan_tables(sn TEXT, table_name TEXT);
+                 INSERT INTO fan_tables VALUES ('SN0002', 'Fan2');",
+            )
+            .unwrap();
+        let devices = read_devices(&connection).unwrap();
+        assert_eq!(
+            devices[0].get_table_warning(),
+            Some(String::from(
+                "Table Fan1 of North 1 is inferred from device order, add it to fan_tables to be sure"
+            ))
+        );
+        assert_eq!(devices[1].get_table_warning(), None);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::sqlite::Device;
+use std::collections::HashMap;
+
+// Timeouts of devices without a policy in the config file. Devices go
+// straight from online to offline after 10 minutes as they always did, the
+// late and stale stages are opted into in the config file.
+const LATE_SECONDS: i64 = 10 * 60;
+const STALE_SECONDS: i64 = 10 * 60;
+const OFFLINE_SECONDS: i64 = 10 * 60;
+
+// How long since its last report a device is shown as late, stale or
+// offline. Late devices missed a report or two, the values of stale devices
+// can no longer be trusted and offline devices are treated as gone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StalenessPolicy {
+    pub late: i64,
+    pub stale: i64,
+    pub offline: i64,
+}
+
+impl Default for StalenessPolicy {
+    fn default() -> StalenessPolicy {
+        StalenessPolicy {
+            late: LATE_SECONDS,
+            stale: STALE_SECONDS,
+            offline: OFFLINE_SECONDS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Staleness {
+    Online,
+    Late,
+    Stale,
+    Offline,
+}
+
+impl Staleness {
+    pub fn label(&self) -> &str {
+        match self {
+            Staleness::Online => "Online",
+            Staleness::Late => "Late",
+            Staleness::Stale => "Stale",
+            Staleness::Offline => "Offline",
+        }
+    }
+}
+
+impl StalenessPolicy {
+    pub fn get_staleness(&self, seconds_since_last: i64) -> Staleness {
+        match seconds_since_last {
+            seconds if seconds > self.offline => Staleness::Offline,
+            seconds if seconds > self.stale => Staleness::Stale,
+            seconds if seconds > self.late => Staleness::Late,
+            _ => Staleness::Online,
+        }
+    }
+
+    // Timeouts for display, e.g. "2m/5m/10m" or "10m/10m/10m" by default
+    pub fn get_summary(&self) -> String {
+        let format = |seconds: i64| match seconds {
+            seconds if seconds % (60 * 60) == 0 => format!("{}h", seconds / (60 * 60)),
+            seconds if seconds % 60 == 0 => format!("{}m", seconds / 60),
+            seconds => format!("{seconds}s"),
+        };
+        format!(
+            "{}/{}/{}",
+            format(self.late),
+            format(self.stale),
+            format(self.offline)
+        )
+    }
+
+    // Checks the timeouts grow from late to offline
+    pub fn validate(&self) -> Result<(), String> {
+        if self.late < 0 {
+            return Err(String::from("late must not be negative"));
+        }
+        if self.late > self.stale {
+            return Err(String::from("late must not be longer than stale"));
+        }
+        if self.stale > self.offline {
+            return Err(String::from("stale must not be longer than offline"));
+        }
+        Ok(())
+    }
+}
+
+// Timeouts given for a group or a device, the others are taken from the
+// broader policy
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PolicyOverride {
+    pub late: Option<i64>,
+    pub stale: Option<i64>,
+    pub offline: Option<i64>,
+}
+
+impl PolicyOverride {
+    // Timeouts that are given win, the others are brought forward or pushed
+    // back so no stage times out before an earlier one
+    pub fn apply(&self, policy: StalenessPolicy) -> StalenessPolicy {
+        let late = self.late.unwrap_or_else(|| {
+            policy
+                .late
+                .min(self.stale.or(self.offline).unwrap_or(i64::MAX))
+        });
+        let stale = self
+            .stale
+            .unwrap_or_else(|| policy.stale.max(late).min(self.offline.unwrap_or(i64::MAX)));
+        let offline = self.offline.unwrap_or_else(|| policy.offline.max(stale));
+        StalenessPolicy {
+            late,
+            stale,
+            offline,
+        }
+    }
+}
+
+// Staleness policies of the config file
+#[derive(Clone, Debug, Default)]
+pub struct StalenessRules {
+    pub default: StalenessPolicy,
+    // Group name to its timeouts
+    pub groups: HashMap<String, PolicyOverride>,
+    // Device name, serial number or MAC to its timeouts
+    pub devices: HashMap<String, PolicyOverride>,
+}
+
+impl StalenessRules {
+    // Device timeouts win over group timeouts, which win over the defaults
+    pub fn get_policy(&self, device: &Device) -> StalenessPolicy {
+        let mut policy = self.default;
+        if let Some(group) = device
+            .group
+            .as_ref()
+            .and_then(|group| self.groups.get(group))
+        {
+            policy = group.apply(policy);
+        }
+        let device = [
+            Some(&device.name),
+            Some(&device.serial_number),
+            device.mac.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(|key| self.devices.get(key));
+        if let Some(device) = device {
+            policy = device.apply(policy);
+        }
+        policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: StalenessPolicy = StalenessPolicy {
+        late: 120,
+        stale: 300,
+        offline: 600,
+    };
+
+    #[test]
+    fn stages_follow_the_timeouts() {
+        assert_eq!(POLICY.get_staleness(120), Staleness::Online);
+        assert_eq!(POLICY.get_staleness(121), Staleness::Late);
+        assert_eq!(POLICY.get_staleness(301), Staleness::Stale);
+        assert_eq!(POLICY.get_staleness(601), Staleness::Offline);
+        let default = StalenessPolicy::default();
+        assert_eq!(default.get_staleness(600), Staleness::Online);
+        assert_eq!(default.get_staleness(601), Staleness::Offline);
+    }
+
+    #[test]
+    fn empty_override_keeps_the_policy() {
+        assert_eq!(PolicyOverride::default().apply(POLICY), POLICY);
+    }
+
+    #[test]
+    fn given_timeouts_win() {
+        let policy = PolicyOverride {
+            late: Some(60),
+            stale: Some(90),
+            offline: Some(3600),
+        }
+        .apply(POLICY);
+        assert_eq!(policy.get_summary(), "1m/90s/1h");
+    }
+
+    #[test]
+    fn timeouts_left_out_are_pushed_back() {
+        let policy = PolicyOverride {
+            late: Some(900),
+            ..PolicyOverride::default()
+        }
+        .apply(POLICY);
+        assert_eq!(policy.get_summary(), "15m/15m/15m");
+    }
+
+    #[test]
+    fn timeouts_left_out_are_brought_forward() {
+        let policy = PolicyOverride {
+            offline: Some(240),
+            ..PolicyOverride::default()
+        }
+        .apply(POLICY);
+        assert_eq!(policy.get_summary(), "2m/4m/4m");
+        let policy = PolicyOverride {
+            stale: Some(60),
+            ..PolicyOverride::default()
+        }
+        .apply(POLICY);
+        assert_eq!(policy.get_summary(), "1m/1m/10m");
+    }
+
+    #[test]
+    fn validates_the_order_of_timeouts() {
+        assert_eq!(POLICY.validate(), Ok(()));
+        let policy = PolicyOverride {
+            late: Some(600),
+            stale: Some(300),
+            ..PolicyOverride::default()
+        }
+        .apply(POLICY);
+        assert_eq!(
+            policy.validate(),
+            Err(String::from("late must not be longer than stale"))
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::alarms::AlarmSeverity;
+use crate::fan_data::{FanData, FieldInfo, FieldValue, Reading, FIELDS};
+use crate::staleness::{Staleness, StalenessPolicy};
+
+// Battery voltages below these need a charge soon / now
+const LOW_BATTERY_VOLTAGE: f32 = 11.8;
+const CRITICAL_BATTERY_VOLTAGE: f32 = 11.0;
+
+// Signal strengths (dBm) below these drop readings now and then / often
+const WEAK_SIGNAL_STRENGTH: f32 = -100.0;
+const CRITICAL_SIGNAL_STRENGTH: f32 = -110.0;
+
+// Motor vibration (mm/s) above these is worth a look / needs the fan stopped
+const HIGH_MOTOR_VIBRATION: f32 = 4.5;
+const CRITICAL_MOTOR_VIBRATION: f32 = 7.1;
+
+// How a value looks to the operator, ordered from least to most urgent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Ok,
+    Unknown,
+    Warning,
+    Critical,
+}
+
+impl From<AlarmSeverity> for Severity {
+    fn from(severity: AlarmSeverity) -> Severity {
+        match severity {
+            AlarmSeverity::Warning => Severity::Warning,
+            AlarmSeverity::Critical => Severity::Critical,
+        }
+    }
+}
+
+// Severity of one field of a reading, from its value or sentinel. Values that
+// look fine become Unknown once the device's readings are stale, since they
+// may have changed since.
+pub fn get_field_severity(
+    field: &FieldInfo,
+    fan_data: &FanData,
+    policy: &StalenessPolicy,
+) -> Severity {
+    if field.key == "last_update" {
+        return get_connection_severity(fan_data, policy);
+    }
+    let severity = match (field.value)(fan_data) {
+        FieldValue::Number(Reading::Ok(value)) => get_number_severity(field.key, value),
+        FieldValue::Number(Reading::Error) => Severity::Critical,
+        FieldValue::Number(Reading::Disabled | Reading::Missing) => Severity::Unknown,
+        FieldValue::Text(_) => get_state_severity(field.key, fan_data),
+    };
+    match (severity, fan_data.get_staleness(policy)) {
+        (Severity::Ok, Ok(Staleness::Online | Staleness::Late)) => Severity::Ok,
+        (Severity::Ok, _) => Severity::Unknown,
+        (severity, _) => severity,
+    }
+}
+
+// A warning while the device is late or stale, critical once it's offline
+pub fn get_connection_severity(fan_data: &FanData, policy: &StalenessPolicy) -> Severity {
+    match fan_data.get_staleness(policy) {
+        Ok(Staleness::Online) => Severity::Ok,
+        Ok(Staleness::Late | Staleness::Stale) => Severity::Warning,
+        Ok(Staleness::Offline) => Severity::Critical,
+        Err(_) => Severity::Unknown,
+    }
+}
+
+// Worst severity of a device's latest reading. Fields the device doesn't
+// report are Unknown and left out, so a weather station isn't greyed out for
+// lacking a motor.
+pub fn get_device_severity(fan_data: Option<&FanData>, policy: &StalenessPolicy) -> Severity {
+    let fan_data = match fan_data {
+        Some(fan_data) => fan_data,
+        None => return Severity::Unknown,
+    };
+    FIELDS
+        .iter()
+        .map(|field| get_field_severity(field, fan_data, policy))
+        .filter(|severity| *severity != Severity::Unknown)
+        .chain([get_connection_severity(fan_data, policy)])
+        .max()
+        .unwrap_or(Severity::Ok)
+}
+
+fn get_number_severity(key: &str, value: f32) -> Severity {
+    match key {
+        "battery_voltage" if value < CRITICAL_BATTERY_VOLTAGE => Severity::Critical,
+        "battery_voltage" if value < LOW_BATTERY_VOLTAGE => Severity::Warning,
+        "signal_strength" if value < CRITICAL_SIGNAL_STRENGTH => Severity::Critical,
+        "signal_strength" if value < WEAK_SIGNAL_STRENGTH => Severity::Warning,
+        "motor_vibration" if value > CRITICAL_MOTOR_VIBRATION => Severity::Critical,
+        "motor_vibration" if value > HIGH_MOTOR_VIBRATION => Severity::Warning,
+        _ => Severity::Ok,
+    }
+}
+
+fn get_state_severity(key: &str, fan_data: &FanData) -> Severity {
+    let flag = match key {
+        "vsd_ready" => fan_data.vsd_error,
+        "vsd_running" => fan_data.vsd_running,
+        "vsd_command" => fan_data.vsd_command,
+        "main_switch" => fan_data.main_switch,
+        "main_panel_door" => fan_data.main_panel_open,
+        "control_door" => fan_data.control_door,
+        "control_method" if fan_data.control_method > 4 => return Severity::Unknown,
+        _ => return Severity::Ok,
+    };
+    match (key, flag) {
+        // Flags the device doesn't report are Unknown, like missing readings
+        (_, None) => Severity::Unknown,
+        ("vsd_ready", Some(true)) => Severity::Critical,
+        ("main_switch", Some(false)) => Severity::Warning,
+        // main_panel_open is set while the door is closed, see get_main_panel_string
+        ("main_panel_door" | "control_door", Some(false)) => Severity::Warning,
+        _ => Severity::Ok,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reading of a device without any fan flags, like a weather station
+    fn get_weather_reading() -> FanData {
+        let now = chrono::offset::Local::now().naive_local();
+        FanData {
+            temperature_top: Reading::Ok(4.0),
+            signal_strength: Reading::Ok(-70.0),
+            battery_voltage: Reading::Ok(12.6),
+            humidity: Reading::Ok(80.0),
+            wind_speed: Reading::Ok(1.5),
+            wind_direction: Reading::Ok(180),
+            rain_meter: Reading::Ok(0.0),
+            ..FanData::new(now.format(crate::fan_data::DATETIME_FORMAT).to_string())
+        }
+    }
+
+    #[test]
+    fn flags_a_device_doesnt_report_are_unknown() {
+        let fan_data = get_weather_reading();
+        for key in ["vsd_ready", "vsd_running", "main_switch", "control_door"] {
+            assert_eq!(
+                get_state_severity(key, &fan_data),
+                Severity::Unknown,
+                "{key}"
+            );
+        }
+        let policy = StalenessPolicy::default();
+        assert_eq!(get_device_severity(Some(&fan_data), &policy), Severity::Ok);
+    }
+
+    #[test]
+    fn reported_flags_keep_their_severity() {
+        let fan_data = FanData {
+            main_switch: Some(false),
+            vsd_error: Some(true),
+            main_panel_open: Some(true),
+            ..get_weather_reading()
+        };
+        assert_eq!(
+            get_state_severity("main_switch", &fan_data),
+            Severity::Warning
+        );
+        assert_eq!(
+            get_state_severity("vsd_ready", &fan_data),
+            Severity::Critical
+        );
+        assert_eq!(
+            get_state_severity("main_panel_door", &fan_data),
+            Severity::Ok
+        );
+        let policy = StalenessPolicy::default();
+        assert_eq!(
+            get_device_severity(Some(&fan_data), &policy),
+            Severity::Critical
+        );
+    }
+
+    #[test]
+    fn running_follows_the_vsd_flag() {
+        let policy = StalenessPolicy::default();
+        let running = |vsd_running, main_switch| {
+            let fan_data = FanData {
+                vsd_running,
+                main_switch,
+                ..get_weather_reading()
+            };
+            fan_data.get_running_status_string(&policy)
+        };
+        assert_eq!(running(Some(true), Some(true)), "Running");
+        assert_eq!(running(Some(true), Some(false)), "Running");
+        assert_eq!(running(Some(true), None), "Running");
+        assert_eq!(running(Some(false), Some(true)), "Not Running");
+        assert_eq!(running(None, Some(true)), "Missing");
+    }
+
+    #[test]
+    fn offline_fans_are_not_running() {
+        let policy = StalenessPolicy::default();
+        let fan_data = FanData {
+            vsd_running: Some(true),
+            ..FanData::new(String::from("2001-01-01 00:00:00"))
+        };
+        assert_eq!(fan_data.is_running(&policy), Ok(Some(false)));
+        let fan_data = FanData {
+            last_update: String::from("yesterday"),
+            ..fan_data
+        };
+        assert!(fan_data.is_running(&policy).is_err());
+    }
+}
@@ -1,380 +1,1562 @@
-use crate::app::App;
+use crate::app::{App, MouseTarget, View};
+use crate::compass::Compass;
+use crate::config::{Panel, PanelItem, Theme};
+use crate::device::Layout as DeviceLayout;
+use crate::fan_data::{
+    FanData, FieldInfo, FieldValue, Reading, BATTERY_VOLTAGE, CONTROL_METHOD, DATETIME_FORMAT,
+    MAIN_SWITCH, RUNNING, TEMPERATURE_BOTTOM, TEMPERATURE_TOP, WIND_SPEED,
+};
+use crate::group::get_group_summary;
+use crate::keymap::{Action, ACTIONS};
+use crate::maintenance::RunStats;
+use crate::staleness::Staleness;
+use crate::status::{get_connection_severity, get_field_severity, Severity};
+use crate::wind::CARDINAL_DIRECTIONS;
+use chrono::NaiveDateTime;
 use tui::{
     backend::Backend,
+    buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    symbols,
     text::{Span, Spans},
-    widgets::{Block, Borders, Paragraph, Tabs},
+    widgets::{
+        Axis, Block, Borders, Cell, Chart, Clear, Dataset, Gauge, GraphType, Paragraph, Row,
+        Sparkline, Table, TableState, Tabs, Widget,
+    },
     Frame,
 };
 
+// Sparkline bars are scaled to SPARKLINE_MIN..=SPARKLINE_MAX, the minimum
+// is the smallest bar a one row sparkline draws
+const SPARKLINE_MIN: u64 = 13;
+const SPARKLINE_MAX: u64 = 100;
+
+// Narrowest value block that fits a sparkline next to the value
+const MIN_INLINE_SPARKLINE_WIDTH: u16 = 24;
+
+// Terminal widths from which the detail view has two and three panel columns
+const MEDIUM_WIDTH: u16 = 80;
+const WIDE_WIDTH: u16 = 120;
+
+// Rows a compass needs to draw its ring, with its block and label
+const MIN_COMPASS_HEIGHT: u16 = 6;
+
 pub fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
     let size = f.size();
-    // Top Level Layout ( splits Tabs & Tab Content)
+    app.mouse_targets.borrow_mut().clear();
+    // Alarm panel grows with the number of active alarms
+    let table_warning = app.get_table_warning();
+    let alarm_rows = (app.alarms.len()
+        + app.error.iter().count()
+        + app.message.iter().count()
+        + table_warning.iter().count())
+    .clamp(1, 6) as u16;
+    // Top Level Layout ( splits Tabs, Tab Content & Alarms)
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(0)
-        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(0),
+                Constraint::Length(alarm_rows + 2),
+            ]
+            .as_ref(),
+        )
         .split(size);
 
-    let block = Block::default().style(Style::default().bg(Color::Black).fg(Color::White));
+    let theme = &app.config.theme;
+    let block = Block::default().style(Style::default().bg(theme.background).fg(theme.foreground));
     f.render_widget(block, size);
 
-    let mut titles: Vec<&str> = Vec::new();
-    for fan in &app.fans {
-        let fan_name = &fan.get_name();
-        let title = fan_name.clone();
-        titles.push(title);
+    draw_tabs(f, app, chunks[0]);
+
+    let selected_device = app.devices.get(app.index);
+    match (app.view, selected_device) {
+        (View::Detail | View::History | View::Maintenance, Some(device))
+            if device.table.is_none() =>
+        {
+            let error = Paragraph::new(device.get_table_error())
+                .style(Style::default().fg(theme.critical))
+                .alignment(Alignment::Center)
+                .block(render_block(device.get_name()));
+            f.render_widget(error, chunks[1]);
+        }
+        (View::Overview, _) => {
+            let rows: Vec<usize> = (0..app.devices.len()).collect();
+            let title = format!(
+                "Overview - {} device details",
+                app.config.keymap.get_hint(Action::OpenDetail)
+            );
+            draw_overview(f, app, chunks[1], &rows, title)
+        }
+        (View::Groups, _) => draw_groups(f, app, chunks[1]),
+        (View::Watchdog, _) => draw_watchdog(f, app, chunks[1]),
+        (View::Detail, _) => draw_detail(f, app, chunks[1]),
+        (View::History, _) => draw_history(f, app, chunks[1]),
+        (View::Maintenance, _) => draw_fan_maintenance(f, app, chunks[1]),
+    }
+
+    draw_alarms(f, app, chunks[2], table_warning);
+
+    if app.search.is_some() {
+        draw_search(f, app, chunks[1]);
+    }
+
+    if app.show_help {
+        draw_help(f, app, size);
     }
-    // Tab Titles
-    let titles = titles
+
+    if app.monochrome {
+        let background = theme.background;
+        f.render_widget(Monochrome { background }, size);
+    }
+}
+
+// Device tabs, only the matches while searching, or group tabs in the groups
+// view. Tabs scroll to keep the selected one in view when the titles don't fit.
+fn draw_tabs<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let theme = &app.config.theme;
+    let device_tab = |index: &usize| {
+        let tab = get_tab(app, app.devices[*index].get_name(), &[*index]);
+        (tab, MouseTarget::Tab(*index))
+    };
+    let help = app.config.keymap.get_hint(Action::Help);
+    let (tabs, selected, mut tabs_title): (Vec<(Spans, MouseTarget)>, usize, String) =
+        match (&app.search, app.view) {
+            (Some(search), _) => (
+                search.matches.iter().map(device_tab).collect(),
+                search.selected,
+                format!(
+                    "Devices - {} of {} matching",
+                    search.matches.len(),
+                    app.devices.len()
+                ),
+            ),
+            (None, View::Groups) => (
+                app.groups
+                    .iter()
+                    .enumerate()
+                    .map(|(group_index, group)| {
+                        let tab = get_tab(app, &group.name, &group.devices);
+                        (tab, MouseTarget::GroupTab(group_index))
+                    })
+                    .collect(),
+                app.group_index,
+                format!("Groups - {help} help"),
+            ),
+            (None, _) => (
+                (0..app.devices.len())
+                    .map(|index| device_tab(&index))
+                    .collect(),
+                app.index,
+                format!("Devices - {help} help"),
+            ),
+        };
+    let (titles, targets): (Vec<Spans>, Vec<MouseTarget>) = tabs.into_iter().unzip();
+
+    // Each title is padded by a space on both sides and followed by a divider
+    let widths: Vec<u16> = titles
         .iter()
-        .map(|t| Spans::from(Span::styled(*t, Style::default().fg(Color::White))))
+        .map(|title| title.width() as u16 + 2)
         .collect();
+    let available = area.width.saturating_sub(2);
+    let get_width = |tabs: &[u16]| tabs.iter().sum::<u16>() + tabs.len().saturating_sub(1) as u16;
+    let mut first = 0;
+    while first < selected && get_width(&widths[first..=selected]) > available {
+        first += 1;
+    }
+    // The selected tab is always shown, Tabs truncates it when it is too wide
+    let mut last = (selected + 1).min(widths.len());
+    while last < widths.len() && get_width(&widths[first..=last]) <= available {
+        last += 1;
+    }
 
     // Creates Tabs and changes selected tab style
-    let tabs = Tabs::new(titles)
-        .block(Block::default().borders(Borders::ALL).title("Fans"))
-        .select(app.index)
-        .style(Style::default().fg(Color::White))
+    if first > 0 || last < titles.len() {
+        tabs_title += &format!(" - {}-{} of {}", first + 1, last, titles.len());
+    }
+    if app.is_loading() {
+        tabs_title += " - Loading...";
+    }
+    let visible: Vec<Spans> = titles.into_iter().take(last).skip(first).collect();
+    let tabs = Tabs::new(visible)
+        .block(Block::default().borders(Borders::ALL).title(tabs_title))
+        .select(selected.saturating_sub(first))
+        .style(Style::default().fg(theme.foreground))
         .highlight_style(
             Style::default()
                 .add_modifier(Modifier::UNDERLINED)
                 .add_modifier(Modifier::BOLD)
-                .fg(Color::LightBlue)
-                .bg(Color::Black),
+                .fg(theme.highlight)
+                .bg(theme.background),
+        );
+    f.render_widget(tabs, area);
+
+    let mut x = area.x + 1;
+    for (target, width) in targets.iter().zip(&widths).take(last).skip(first) {
+        let width = (*width).min((area.right() - 1).saturating_sub(x));
+        if width == 0 {
+            break;
+        }
+        let tab = Rect::new(x, area.y + 1, width, 1);
+        add_mouse_target(app, tab, *target);
+        x += width + 1;
+    }
+}
+
+// Tab title of one or more devices, colored by their worst status, marked
+// while any has active alarms and flashing when any reported new readings
+fn get_tab<'a>(app: &App, name: &'a str, devices: &[usize]) -> Spans<'a> {
+    let severity = devices
+        .iter()
+        .map(|index| app.get_device_severity(*index))
+        .max()
+        .unwrap_or(Severity::Ok);
+    let mut style = match severity {
+        Severity::Ok => Style::default().fg(app.config.theme.foreground),
+        severity => get_severity_style(app, severity),
+    };
+    if devices.iter().any(|index| app.is_updated(*index)) {
+        style = style.bg(app.config.theme.selection);
+    }
+    match devices
+        .iter()
+        .any(|index| app.get_alarm_severity(*index).is_some())
+    {
+        true => Spans::from(Span::styled(format!("! {name}"), style)),
+        false => Spans::from(Span::styled(name, style)),
+    }
+}
+
+// Query and matching devices under the tab bar, the highlighted match first
+// in view
+fn draw_search<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let search = match &app.search {
+        Some(search) => search,
+        None => return,
+    };
+    let width = area.width.min(64);
+    let height = (search.matches.len() as u16 + 3)
+        .min(area.height)
+        .max(3.min(area.height));
+    let popup = Rect::new(area.x + (area.width - width) / 2, area.y, width, height);
+    let rows = height.saturating_sub(3) as usize;
+    let first = (search.selected + 1).saturating_sub(rows);
+
+    let mut lines = vec![Spans::from(vec![
+        Span::styled("/", Style::default().fg(app.config.theme.highlight)),
+        Span::raw(search.query.clone()),
+    ])];
+    for (position, index) in search.matches.iter().enumerate().skip(first).take(rows) {
+        let device = &app.devices[*index];
+        let line = format!(
+            "{:<24} {:<12} {}",
+            device.get_name(),
+            device.serial_number,
+            device.mac.as_deref().unwrap_or("")
         );
-    f.render_widget(tabs, chunks[0]);
+        let style = match position == search.selected {
+            true => Style::default()
+                .bg(app.config.theme.selection)
+                .add_modifier(Modifier::BOLD),
+            false => Style::default(),
+        };
+        lines.push(Spans::from(Span::styled(line, style)));
+    }
+    if search.matches.is_empty() {
+        lines.push(Spans::from(Span::styled(
+            "No matching devices",
+            get_severity_style(app, Severity::Unknown),
+        )));
+    }
+    let block = render_block("Search - [Enter] jump - [Esc] cancel").style(
+        Style::default()
+            .bg(app.config.theme.background)
+            .fg(app.config.theme.foreground),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
+    // Cursor after the query
+    let cursor = popup.x + 2 + search.query.chars().count() as u16;
+    if popup.height > 2 && cursor < popup.right() - 1 {
+        f.set_cursor(cursor, popup.y + 1);
+    }
+}
 
-    let (left_chunks, middle_chunks, right_chunks) = get_tab_content_chunks(chunks[1]);
+// Strips every color once the frame is drawn. Cells on a colored background,
+// such as gauge fills and the selected row, are reversed instead.
+struct Monochrome {
+    background: Color,
+}
 
-    // Left Blocks
-    let left_middle = Block::default().title("Middle Left").borders(Borders::ALL);
-    f.render_widget(left_middle, left_chunks[1]);
+impl Widget for Monochrome {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                if cell.bg != Color::Reset && cell.bg != self.background {
+                    cell.modifier.insert(Modifier::REVERSED);
+                }
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+            }
+        }
+    }
+}
 
-    let left_bottom = Block::default().title("Bottom Left").borders(Borders::ALL);
-    f.render_widget(left_bottom, left_chunks[2]);
+// Popup listing the keys of every action, centered over the whole screen
+fn draw_help<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let keymap = &app.config.keymap;
+    let mut rows = Vec::new();
+    let mut device_keys = Vec::new();
+    for info in &ACTIONS {
+        let keys = keymap.get_keys(info.action);
+        // The device jumps share one row
+        if let Action::Device(_) = info.action {
+            device_keys.extend(keys);
+            continue;
+        }
+        rows.push((keys.join(" "), info.description.to_string()));
+    }
+    rows.push((device_keys.join(" "), String::from("Jump to device 1-9")));
+    rows.push((
+        String::from("Click"),
+        String::from("Select a tab, chart a value"),
+    ));
+    rows.push((
+        String::from("Scroll"),
+        String::from("Zoom a chart, scroll panels"),
+    ));
 
-    // Top Left
-    // Information Block Container
-    let left_top = Block::default()
-        .title("General Information")
-        .borders(Borders::ALL);
-    f.render_widget(left_top, left_chunks[0]);
-    let (battery_voltage, connection_status, last_update) = get_information_values(&app);
-    let left_top_inner = get_block_content_chunks(left_chunks[0]);
-    let battery_voltage = render_block_with_content("Battery Voltage", &battery_voltage);
-    let connection_status = render_block_with_content("Connection Status", &connection_status);
-    let last_update = render_block_with_content("Last Update", &last_update);
-    f.render_widget(battery_voltage, left_top_inner[1]);
-    f.render_widget(connection_status, left_top_inner[2]);
-    f.render_widget(last_update, left_top_inner[3]);
-
-    // Left Middle
-    // Fan Status Block Container
-    let left_top = Block::default().title("Fan Status").borders(Borders::ALL);
-    f.render_widget(left_top, left_chunks[1]);
-    let (running, error, command) = get_fan_status_values(&app);
-    let left_middle_inner = get_block_content_chunks(left_chunks[1]);
-    let last_update = render_block_with_content("Ready Status", &error);
-    let signal_strength = render_block_with_content("Running Status", &running);
-    let control_mode = render_block_with_content("C&M Command", &command);
-    f.render_widget(last_update, left_middle_inner[1]);
-    f.render_widget(signal_strength, left_middle_inner[2]);
-    f.render_widget(control_mode, left_middle_inner[3]);
-
-    // Left Bottom
-    // Panel Switch & Door Status Block Container
-    let left_top = Block::default()
-        .title("Panel Switch & Door Status")
-        .borders(Borders::ALL);
-    f.render_widget(left_top, left_chunks[2]);
-    let (main_switch, main_panel_door, cm_panel_door) = get_panel_door_values(&app);
-    let left_bottom_inner = get_block_content_chunks(left_chunks[2]);
-    let main_switch = render_block_with_content("Main Switch", &main_switch);
-    let main_panel_door = render_block_with_content("Main Panel Door", &main_panel_door);
-    let cm_panel_door = render_block_with_content("C&M Panel Door", &cm_panel_door);
-    f.render_widget(main_switch, left_bottom_inner[1]);
-    f.render_widget(main_panel_door, left_bottom_inner[2]);
-    f.render_widget(cm_panel_door, left_bottom_inner[3]);
-
-    // Top Middle
-    // Temperature block
-    let middle_top = render_block("Temperature");
-    f.render_widget(middle_top, middle_chunks[0]);
-    let (temperature_top, temperature_bottom, temperature_far) = get_temperature_values(&app);
-    let middle_top_inner = get_block_content_chunks(middle_chunks[0]);
-    let temperature_top = render_block_with_content("Temperature Top", &temperature_top);
-    let temperature_bottom = render_block_with_content("Temperature Bottom", &temperature_bottom);
-    let temperature_far = render_block_with_content("Temperature Far", &temperature_far);
-    f.render_widget(temperature_top, middle_top_inner[1]);
-    f.render_widget(temperature_bottom, middle_top_inner[2]);
-    f.render_widget(temperature_far, middle_top_inner[3]);
-
-    // Middle Middle
-    // Fan Data
-    let middle_middle = render_block("Fan Data");
-    f.render_widget(middle_middle, middle_chunks[1]);
-    let (motor_current, motor_vibration, main_state) = get_fan_data_values(&app);
-    let middle_middle_inner = get_block_content_chunks(middle_chunks[1]);
-    let motor_current = render_block_with_content("Motor Current", &motor_current);
-    let motor_vibration = render_block_with_content("Motor Vibration", &motor_vibration);
-    let main_state = render_block_with_content("Main State", &main_state);
-    f.render_widget(motor_current, middle_middle_inner[1]);
-    f.render_widget(motor_vibration, middle_middle_inner[2]);
-    f.render_widget(main_state, middle_middle_inner[3]);
-
-    // Middle Bottom
-    // Main Panel Status
-    let middle_bottom = render_block("Main Panel Status");
-    f.render_widget(middle_bottom, middle_chunks[2]);
-    let (operating_mode, running_status) = get_main_panel_values(&app);
-    let middle_middle_inner = get_block_content_chunks(middle_chunks[2]);
-    let operating_mode = render_block_with_content("Control Method", &operating_mode);
-    let running_status = render_block_with_content("Fan Status", &running_status);
-    f.render_widget(operating_mode, middle_middle_inner[1]);
-    f.render_widget(running_status, middle_middle_inner[2]);
-
-    // let middle_bottom = render_block("Middle Bottom");
-    // f.render_widget(middle_bottom, middle_chunks[2]);
-
-    // Right Top
-    // Environmental Data
-    let right_top = render_block("Environmental Data");
-    f.render_widget(right_top, right_chunks[0]);
-    let (humidity, wind_speed, wind_direction) = get_environment_values(&app);
-    let middle_right_inner = get_block_content_chunks(right_chunks[0]);
-    let humidity = render_block_with_content("Humidity", &humidity);
-    let wind_speed = render_block_with_content("Wind Speed", &wind_speed);
-    let wind_direction = render_block_with_content("Wind Direction", &wind_direction);
-    f.render_widget(humidity, middle_right_inner[1]);
-    f.render_widget(wind_speed, middle_right_inner[2]);
-    f.render_widget(wind_direction, middle_right_inner[3]);
-
-    // Right Middle
-    // Fan Data
-    let middle_right = render_block("Additional Data");
-    f.render_widget(middle_right, right_chunks[1]);
-    let (signal_strength, rain_meter) = get_additional_values(&app);
-    let middle_right_inner = get_block_content_chunks(right_chunks[1]);
-    let signal_strength = render_block_with_content("Signal Strength", &signal_strength);
-    let rain_meter = render_block_with_content("Rain Meter", &rain_meter);
-    f.render_widget(signal_strength, middle_right_inner[1]);
-    f.render_widget(rain_meter, middle_right_inner[2]);
-
-    // let right_bottom = render_block("Bottom Right");
-    // f.render_widget(right_bottom, right_chunks[2]);
-}
-
-fn render_block(title: &str) -> Block {
-    let block = Block::default().title(title).borders(Borders::ALL);
-    block
-}
-
-fn render_block_with_content<'a>(title: &'a str, content: &'a str) -> Paragraph<'a> {
-    let block = Block::default()
-        .style(Style::default().fg(Color::Blue))
-        .title(title)
-        .borders(Borders::ALL);
-    let paragraph = Paragraph::new(content)
-        .block(block)
-        .style(Style::default())
-        .alignment(Alignment::Center);
-    paragraph
+    let key_width = rows
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Spans> = rows
+        .into_iter()
+        .map(|(keys, description)| {
+            Spans::from(vec![
+                Span::styled(
+                    format!("{keys:<key_width$}  "),
+                    Style::default()
+                        .fg(app.config.theme.highlight)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(description),
+            ])
+        })
+        .collect();
+    let width =
+        (lines.iter().map(|line| line.width()).max().unwrap_or(0) as u16 + 4).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    let block = render_block("Help - any key closes").style(
+        Style::default()
+            .bg(app.config.theme.background)
+            .fg(app.config.theme.foreground),
+    );
+    f.render_widget(Clear, popup);
+    f.render_widget(Paragraph::new(lines).block(block), popup);
 }
 
-fn get_temperature_values(app: &App) -> (String, String, String) {
-    let fan_data = &app.fan_data;
-    let (temp_high, temp_low, temp_far) = match fan_data {
-        Some(dta) => (
-            dta.get_temperature_top_string(),
-            dta.get_temperature_bottom_string(),
-            dta.get_temperature_far_string(),
+fn draw_alarms<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect, table_warning: Option<String>) {
+    let mut lines = Vec::new();
+    if let Some(error) = &app.error {
+        lines.push(Spans::from(Span::styled(
+            format!("Database error: {error}"),
+            get_severity_style(app, Severity::Critical),
+        )));
+    }
+    if let Some(warning) = table_warning {
+        lines.push(Spans::from(Span::styled(
+            warning,
+            get_severity_style(app, Severity::Warning),
+        )));
+    }
+    if let Some(message) = &app.message {
+        lines.push(Spans::from(Span::styled(
+            message.clone(),
+            Style::default().fg(app.config.theme.highlight),
+        )));
+    }
+    for alarm in &app.alarms {
+        let fan_name = match app.devices.get(alarm.fan_index) {
+            Some(device) => device.get_name(),
+            None => "Unknown",
+        };
+        let style = get_severity_style(app, Severity::from(alarm.severity));
+        lines.push(Spans::from(vec![
+            Span::styled(format!("{:<10}", alarm.severity.label()), style),
+            Span::styled(format!("{:<24}", fan_name), Style::default()),
+            Span::styled(alarm.name.clone(), style),
+        ]));
+    }
+    if lines.is_empty() {
+        lines.push(Spans::from(Span::styled(
+            "No active alarms",
+            Style::default().fg(app.config.theme.ok),
+        )));
+    }
+    let title = format!("Alarms ({})", app.alarms.len());
+    let paragraph = Paragraph::new(lines).block(render_block_owned(title));
+    f.render_widget(paragraph, area);
+}
+
+// Text style of a status. Without colors statuses are told apart by weight
+// alone, Ok values are left plain so problems stand out.
+fn get_severity_style(app: &App, severity: Severity) -> Style {
+    let theme = &app.config.theme;
+    match (app.monochrome, severity) {
+        (_, Severity::Ok) => Style::default(),
+        (false, Severity::Unknown) => Style::default().fg(theme.unknown),
+        (false, Severity::Warning) => Style::default().fg(theme.warning),
+        (false, Severity::Critical) => Style::default()
+            .fg(theme.critical)
+            .add_modifier(Modifier::BOLD),
+        (true, Severity::Unknown) => Style::default().add_modifier(Modifier::DIM),
+        (true, Severity::Warning) => Style::default().add_modifier(Modifier::BOLD),
+        (true, Severity::Critical) => Style::default()
+            .add_modifier(Modifier::BOLD)
+            .add_modifier(Modifier::REVERSED),
+    }
+}
+
+// Border style of a value block, the theme's border color unless the value
+// needs attention
+fn get_border_style(app: &App, severity: Severity) -> Style {
+    match severity {
+        Severity::Ok => Style::default().fg(app.config.theme.border),
+        severity => get_severity_style(app, severity),
+    }
+}
+
+fn get_selected_severity(app: &App, field: &FieldInfo) -> Severity {
+    match &app.fan_data {
+        Some(dta) => get_field_severity(field, dta, &app.get_staleness_policy(app.index)),
+        None => Severity::Unknown,
+    }
+}
+
+fn get_selected_connection_severity(app: &App) -> Severity {
+    match &app.fan_data {
+        Some(dta) => get_connection_severity(dta, &app.get_staleness_policy(app.index)),
+        None => Severity::Unknown,
+    }
+}
+
+// Latest reading of each device in rows
+fn draw_overview<B: Backend>(
+    f: &mut Frame<B>,
+    app: &App,
+    area: Rect,
+    rows: &[usize],
+    title: String,
+) {
+    // Name, then the columns after it by index, fewer of them on narrow terminals
+    let (columns, widths) = match f.size().width {
+        width if width < MEDIUM_WIDTH => (
+            vec![0, 4, 5],
+            vec![
+                Constraint::Percentage(30),
+                Constraint::Percentage(24),
+                Constraint::Percentage(22),
+                Constraint::Percentage(24),
+            ],
         ),
-        None => (
-            String::from("No Data"),
-            String::from("No Data"),
-            String::from("No Data"),
+        width if width < WIDE_WIDTH => (
+            vec![0, 1, 4, 5, 6],
+            vec![
+                Constraint::Percentage(24),
+                Constraint::Percentage(14),
+                Constraint::Percentage(16),
+                Constraint::Percentage(14),
+                Constraint::Percentage(10),
+                Constraint::Percentage(22),
+            ],
+        ),
+        _ => (
+            (0..7).collect(),
+            vec![
+                Constraint::Percentage(18),
+                Constraint::Percentage(10),
+                Constraint::Percentage(10),
+                Constraint::Percentage(16),
+                Constraint::Percentage(10),
+                Constraint::Percentage(10),
+                Constraint::Percentage(8),
+                Constraint::Percentage(16),
+            ],
         ),
     };
-    (temp_high, temp_low, temp_far)
+    let headers = [
+        "Connection",
+        "Running",
+        "Control Method",
+        "Temp Top",
+        "Temp Bottom",
+        "Battery",
+        "Last Update",
+    ];
+    let mut header = vec!["Name"];
+    header.extend(columns.iter().map(|column| headers[*column]));
+    let header = Row::new(header)
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .bottom_margin(1);
+
+    let devices = rows;
+    let mut rows = Vec::new();
+    for index in devices {
+        // Devices are listed once the first overview arrived
+        let (device, fan_data) = match app.overview.get(*index) {
+            Some(fan_data) => (&app.devices[*index], fan_data),
+            None => continue,
+        };
+        let is_fan = device.device_type.layout == DeviceLayout::Fan;
+        let policy = &device.staleness;
+        let values = match fan_data {
+            Some(dta) => {
+                let field = |field: &FieldInfo| {
+                    ((field.display)(dta), get_field_severity(field, dta, policy))
+                };
+                vec![
+                    (
+                        dta.get_connection_status_string(policy),
+                        get_connection_severity(dta, policy),
+                    ),
+                    // Only fans have a motor to report on
+                    match is_fan {
+                        true => (
+                            dta.get_running_status_string(policy),
+                            get_field_severity(&RUNNING, dta, policy),
+                        ),
+                        false => (String::from("-"), Severity::Ok),
+                    },
+                    match is_fan {
+                        true => field(&CONTROL_METHOD),
+                        false => (String::from("-"), Severity::Ok),
+                    },
+                    field(&TEMPERATURE_TOP),
+                    field(&TEMPERATURE_BOTTOM),
+                    field(&BATTERY_VOLTAGE),
+                    (
+                        dta.get_last_update_string(),
+                        get_connection_severity(dta, policy),
+                    ),
+                ]
+            }
+            None if device.table.is_none() => {
+                vec![(String::from("No Table"), Severity::Critical); 7]
+            }
+            None => vec![(String::from("No Data"), Severity::Unknown); 7],
+        };
+        let mut cells = vec![Cell::from(device.get_name().to_string())];
+        for column in &columns {
+            let (value, severity) = values[*column].clone();
+            cells.push(Cell::from(value).style(get_severity_style(app, severity)));
+        }
+        rows.push(Row::new(cells));
+    }
+
+    let table = Table::new(rows)
+        .header(header)
+        .block(render_block_owned(title))
+        .highlight_style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .bg(app.config.theme.selection),
+        )
+        .highlight_symbol("> ")
+        .widths(&widths);
+    let mut state = TableState::default();
+    state.select(devices.iter().position(|index| *index == app.index));
+    f.render_stateful_widget(table, area, &mut state);
 }
 
-fn get_information_values(app: &App) -> (String, String, String) {
-    let fan_data = &app.fan_data;
-    match fan_data {
-        Some(dta) => (
-            dta.get_voltage_string(),
-            dta.get_connection_status_string(),
-            dta.get_last_update_string(),
-        ),
-        None => (
-            String::from("No Data"),
-            String::from("No Data"),
-            String::from("No Data"),
-        ),
+// Aggregates of the selected group above the overview of its devices
+fn draw_groups<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let group = match app.groups.get(app.group_index) {
+        Some(group) => group,
+        None => return,
+    };
+    let summary = get_group_summary(group, &app.devices, &app.overview);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 3); 3].as_ref())
+        .split(chunks[0]);
+
+    let running = format!("{} of {}", summary.running, summary.fans);
+    let offline = summary.offline.to_string();
+    let offline_severity = match summary.offline {
+        0 => Severity::Ok,
+        _ => Severity::Critical,
+    };
+    let (temperature, temperature_severity) = match summary.min_temperature_bottom {
+        Some((temperature, index)) => {
+            let value = Reading::Ok(temperature).format_with_unit(TEMPERATURE_BOTTOM.unit);
+            // Names don't fit next to the value on narrow terminals
+            let value = match f.size().width {
+                width if width < MEDIUM_WIDTH => value,
+                _ => format!("{value} - {}", app.devices[index].get_name()),
+            };
+            match temperature < app.frost_threshold {
+                true => (value, Severity::Warning),
+                false => (value, Severity::Ok),
+            }
+        }
+        None => (String::from("No Data"), Severity::Unknown),
+    };
+    let blocks = [
+        ("Fans Running", &running, Severity::Ok),
+        ("Fans Offline", &offline, offline_severity),
+        ("Min Temp Bottom", &temperature, temperature_severity),
+    ];
+    for ((title, value, severity), area) in blocks.into_iter().zip(columns) {
+        f.render_widget(render_block_with_content(app, title, value, severity), area);
     }
+
+    let title = format!(
+        "{} - {} device details - {} all devices",
+        group.name,
+        app.config.keymap.get_hint(Action::OpenDetail),
+        app.config.keymap.get_hint(Action::Groups)
+    );
+    draw_overview(f, app, chunks[1], &group.devices, title);
 }
 
-fn get_fan_status_values(app: &App) -> (String, String, String) {
-    let fan_data = &app.fan_data;
-    match fan_data {
-        Some(dta) => (
-            dta.get_running_string(),
-            dta.get_ready_string(),
-            dta.get_command_string(),
+// Every device by time since its last report, so radio and power dropouts
+// stand out at the top
+fn draw_watchdog<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    // Name, then the columns after it by index, fewer of them on narrow terminals
+    let (columns, widths) = match f.size().width {
+        width if width < MEDIUM_WIDTH => (
+            vec![1, 2],
+            vec![
+                Constraint::Percentage(40),
+                Constraint::Percentage(30),
+                Constraint::Percentage(30),
+            ],
+        ),
+        width if width < WIDE_WIDTH => (
+            vec![0, 1, 2, 4],
+            vec![
+                Constraint::Percentage(26),
+                Constraint::Percentage(20),
+                Constraint::Percentage(14),
+                Constraint::Percentage(16),
+                Constraint::Percentage(24),
+            ],
         ),
-        None => (
-            String::from("No Data"),
-            String::from("No Data"),
-            String::from("No Data"),
+        _ => (
+            (0..5).collect(),
+            vec![
+                Constraint::Percentage(20),
+                Constraint::Percentage(16),
+                Constraint::Percentage(10),
+                Constraint::Percentage(14),
+                Constraint::Percentage(20),
+                Constraint::Percentage(20),
+            ],
         ),
+    };
+    let headers = [
+        "Group",
+        "Status",
+        "Silent For",
+        "Last Report",
+        "Late/Stale/Offline",
+    ];
+    let mut header = vec!["Name"];
+    header.extend(columns.iter().map(|column| headers[*column]));
+    let header = Row::new(header)
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .bottom_margin(1);
+
+    let devices = app.get_rows();
+    let mut rows = Vec::new();
+    // Devices that are late, stale and offline, or never reported
+    let mut counts = [0; 3];
+    for index in &devices {
+        // Devices are listed once the first overview arrived
+        let (device, fan_data) = match app.overview.get(*index) {
+            Some(fan_data) => (&app.devices[*index], fan_data),
+            None => continue,
+        };
+        let policy = &device.staleness;
+        let (status, last_report, severity) = match fan_data {
+            Some(dta) => match dta.get_staleness(policy) {
+                Ok(staleness) => {
+                    let severity = get_connection_severity(dta, policy);
+                    let seconds = app.get_seconds_since_last(*index);
+                    (
+                        staleness,
+                        Some((seconds, dta.last_update.clone())),
+                        severity,
+                    )
+                }
+                Err(_) => (Staleness::Offline, None, Severity::Unknown),
+            },
+            None => (Staleness::Offline, None, Severity::Critical),
+        };
+        match status {
+            Staleness::Online => {}
+            Staleness::Late => counts[0] += 1,
+            Staleness::Stale => counts[1] += 1,
+            Staleness::Offline => counts[2] += 1,
+        }
+        let status = match (fan_data, &last_report) {
+            (None, _) if device.table.is_none() => String::from("No Table"),
+            (None, _) => String::from("No Data"),
+            (Some(_), None) => String::from("Parsing error"),
+            (Some(_), Some(_)) => status.label().to_string(),
+        };
+        let (silent_for, last_report) = match last_report {
+            Some((seconds, last_report)) => (format_elapsed(seconds), last_report),
+            None => (String::from("-"), String::from("-")),
+        };
+        let values = [
+            (
+                device.group.clone().unwrap_or_else(|| String::from("-")),
+                Severity::Ok,
+            ),
+            (status, severity),
+            (silent_for, severity),
+            (last_report, Severity::Ok),
+            (policy.get_summary(), Severity::Ok),
+        ];
+        let mut cells = vec![Cell::from(device.get_name().to_string())];
+        for column in &columns {
+            let (value, severity) = values[*column].clone();
+            cells.push(Cell::from(value).style(get_severity_style(app, severity)));
+        }
+        rows.push(Row::new(cells));
     }
+
+    let title = format!(
+        "Watchdog - {} late, {} stale, {} offline - {} device details - {} close",
+        counts[0],
+        counts[1],
+        counts[2],
+        app.config.keymap.get_hint(Action::OpenDetail),
+        app.config.keymap.get_hint(Action::Watchdog)
+    );
+    let table = Table::new(rows)
+        .header(header)
+        .block(render_block_owned(title))
+        .highlight_style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .bg(app.config.theme.selection),
+        )
+        .highlight_symbol("> ")
+        .widths(&widths);
+    let mut state = TableState::default();
+    state.select(devices.iter().position(|index| *index == app.index));
+    f.render_stateful_widget(table, area, &mut state);
 }
 
-fn get_environment_values(app: &App) -> (String, String, String) {
-    let fan_data = &app.fan_data;
-    match fan_data {
-        Some(dta) => (
-            dta.get_humidity_string(),
-            dta.get_wind_speed_string(),
-            dta.get_wind_direction_string(),
-        ),
-        None => (
-            String::from("No Data"),
-            String::from("No Data"),
-            String::from("No Data"),
+// Time since a report, e.g. 45s, 12m 05s, 3h 20m or 2d 04h
+fn format_elapsed(seconds: i64) -> String {
+    match seconds.max(0) {
+        seconds if seconds < 60 => format!("{seconds}s"),
+        seconds if seconds < 60 * 60 => format!("{}m {:02}s", seconds / 60, seconds % 60),
+        seconds if seconds < 60 * 60 * 24 => {
+            format!("{}h {:02}m", seconds / (60 * 60), seconds % (60 * 60) / 60)
+        }
+        seconds => format!(
+            "{}d {:02}h",
+            seconds / (60 * 60 * 24),
+            seconds % (60 * 60 * 24) / (60 * 60)
         ),
     }
 }
 
-fn get_additional_values(app: &App) -> (String, String) {
-    let fan_data = &app.fan_data;
-    match fan_data {
-        Some(dta) => (
-            dta.get_signal_strength_string(),
-            dta.get_rain_meter_string(),
-        ),
-        None => (String::from("No Data"), String::from("No Data")),
+// Panels of the selected device's layout from the config, filling the view
+// left to right and top to bottom. Panels are drawn full size when an even
+// grid fits them, otherwise as lists of values that scroll by panel row.
+fn draw_detail<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    add_mouse_target(app, area, MouseTarget::Detail);
+    let panels = app.config.get_panels(get_selected_layout(app));
+    let columns = get_panel_columns(f.size());
+    let rows: Vec<&[Panel]> = panels.chunks(columns).collect();
+    let row_height = area.height / rows.len().max(1) as u16;
+    let fits = rows
+        .iter()
+        .flat_map(|row| row.iter())
+        .all(|panel| get_panel_height(panel, false) <= row_height);
+    if fits {
+        app.detail_overflow.set(0);
+        let chunks = get_panel_chunks(area, rows.len(), columns);
+        for (panel, chunk) in panels.iter().zip(chunks) {
+            draw_panel(f, app, chunk, panel);
+        }
+        return;
+    }
+
+    // Rows are as tall as their tallest condensed panel
+    let heights: Vec<u16> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|panel| get_panel_height(panel, true))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    // The bottom line tells there is more to scroll to
+    let total: u16 = heights.iter().sum();
+    let available = match total > area.height {
+        true => area.height.saturating_sub(1),
+        false => area.height,
+    };
+    // First row from which the remaining rows fill the view
+    let mut overflow = heights.len();
+    let mut height = 0;
+    while overflow > 0 && height + heights[overflow - 1] <= available {
+        overflow -= 1;
+        height += heights[overflow];
+    }
+    // The last row can always be scrolled to, even when it is taller than the view
+    let overflow = overflow.min(heights.len().saturating_sub(1));
+    app.detail_overflow.set(overflow);
+
+    let first = app.detail_scroll.min(overflow);
+    let mut y = area.y;
+    let mut shown = 0;
+    for (row, height) in rows.iter().zip(&heights).skip(first) {
+        // The first row is drawn clipped when it is taller than the view
+        let bottom = area.y + available;
+        let height = match shown {
+            0 => (*height).min(bottom - y),
+            _ if y + height > bottom => break,
+            _ => *height,
+        };
+        if height == 0 {
+            break;
+        }
+        let row_area = Rect::new(area.x, y, area.width, height);
+        let chunks = get_panel_chunks(row_area, 1, columns);
+        for (panel, chunk) in row.iter().zip(chunks) {
+            draw_condensed_panel(f, app, chunk, panel);
+        }
+        y += height;
+        shown += 1;
+    }
+    if available < area.height {
+        let hint = format!(
+            "{}/{} scroll - rows {}-{} of {}",
+            app.config.keymap.get_hint(Action::Up),
+            app.config.keymap.get_hint(Action::Down),
+            first + 1,
+            first + shown.max(1),
+            rows.len()
+        );
+        let hint = Paragraph::new(hint)
+            .style(get_severity_style(app, Severity::Unknown))
+            .alignment(Alignment::Center);
+        f.render_widget(hint, Rect::new(area.x, area.bottom() - 1, area.width, 1));
+    }
+}
+
+// Panel columns for the terminal size, narrow terminals such as phones get one
+fn get_panel_columns(size: Rect) -> usize {
+    match size.width {
+        width if width >= WIDE_WIDTH => 3,
+        width if width >= MEDIUM_WIDTH => 2,
+        _ => 1,
+    }
+}
+
+// Rows a panel needs with its borders, condensed panels use a line per value
+fn get_panel_height(panel: &Panel, condensed: bool) -> u16 {
+    let items: u16 = panel
+        .items
+        .iter()
+        .map(|item| match (condensed, item) {
+            (true, _) => 1,
+            (false, PanelItem::Wind | PanelItem::WindRose) => MIN_COMPASS_HEIGHT,
+            (false, _) => 3,
+        })
+        .sum();
+    items + 2
+}
+
+fn draw_panel<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect, panel: &Panel) {
+    let title = panel.title.replace("{window}", app.history_window.label());
+    f.render_widget(render_block_owned(title), area);
+    let chunks = get_panel_item_chunks(area, &panel.items);
+    for (item, chunk) in panel.items.iter().zip(chunks) {
+        draw_panel_item(f, app, chunk, item);
+    }
+}
+
+// Panel as a list of "Label: value" lines, values styled by their status
+fn draw_condensed_panel<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect, panel: &Panel) {
+    let title = panel.title.replace("{window}", app.history_window.label());
+    // Lines of fields open their chart like value blocks do
+    for (index, item) in panel.items.iter().enumerate() {
+        let y = area.y + 1 + index as u16;
+        if let (PanelItem::Field(field), true) = (item, y + 1 < area.bottom()) {
+            let line = Rect::new(area.x + 1, y, area.width.saturating_sub(2), 1);
+            add_mouse_target(app, line, MouseTarget::Metric(field));
+        }
+    }
+    let lines: Vec<Spans> = panel
+        .items
+        .iter()
+        .map(|item| {
+            let (label, value, severity) = get_panel_item_value(app, item);
+            Spans::from(vec![
+                Span::raw(format!("{label}: ")),
+                Span::styled(value, get_severity_style(app, severity)),
+            ])
+        })
+        .collect();
+    let paragraph = Paragraph::new(lines).block(render_block_owned(title));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_panel_item<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect, item: &PanelItem) {
+    match item {
+        PanelItem::Field(field) => draw_metric(f, app, area, field),
+        PanelItem::Wind => {
+            let key = app.config.keymap.get_hint(Action::WindRose);
+            let title = match app.show_wind_rose {
+                true => format!("Wind Rose - {key} current wind"),
+                false => format!("Wind - {key} wind rose"),
+            };
+            draw_wind_compass(f, app, area, &title, app.show_wind_rose);
+        }
+        PanelItem::WindRose => draw_wind_compass(f, app, area, "Wind Rose", true),
+        item => {
+            let (title, value, severity) = get_panel_item_value(app, item);
+            f.render_widget(
+                render_block_with_content(app, title, &value, severity),
+                area,
+            );
+        }
     }
 }
-// TODO Fix Main State
-fn get_fan_data_values(app: &App) -> (String, String, String) {
-    let fan_data = &app.fan_data;
-    match fan_data {
-        Some(dta) => (
-            dta.get_motor_current_string(),
-            dta.get_motor_vibration_string(),
-            dta.get_main_switch_string(),
+
+// Label, displayed value and status of a panel item
+fn get_panel_item_value(app: &App, item: &PanelItem) -> (&'static str, String, Severity) {
+    let text = |value: &dyn Fn(&FanData) -> String| match &app.fan_data {
+        Some(dta) => value(dta),
+        None => String::from("No Data"),
+    };
+    let policy = app.get_staleness_policy(app.index);
+    match item {
+        PanelItem::Field(field) => (
+            field.label,
+            text(&field.display),
+            get_selected_severity(app, field),
+        ),
+        PanelItem::Wind if app.show_wind_rose => {
+            ("Wind Rose", get_wind_rose_label(app), Severity::Ok)
+        }
+        PanelItem::Wind => ("Wind", get_wind_label(app), Severity::Ok),
+        PanelItem::WindRose => ("Wind Rose", get_wind_rose_label(app), Severity::Ok),
+        PanelItem::ConnectionStatus => (
+            "Connection Status",
+            text(&|dta| dta.get_connection_status_string(&policy)),
+            get_selected_connection_severity(app),
+        ),
+        PanelItem::RunningStatus => (
+            "Fan Status",
+            text(&|dta| dta.get_running_status_string(&policy)),
+            get_selected_severity(app, &RUNNING),
         ),
-        None => (
-            String::from("No Data"),
-            String::from("No Data"),
-            String::from("No Data"),
+        // TODO Fix Main State
+        PanelItem::MainState => (
+            "Main State",
+            text(&FanData::get_main_switch_string),
+            get_selected_severity(app, &MAIN_SWITCH),
+        ),
+        PanelItem::Inversion => (
+            "Inversion (Top - Bottom)",
+            text(&FanData::get_inversion_string),
+            Severity::Ok,
+        ),
+        PanelItem::FarDifference => (
+            "Far - Bottom",
+            text(&FanData::get_far_difference_string),
+            Severity::Ok,
+        ),
+        // An ongoing frost event is what the fans are there for, but worth a look
+        PanelItem::FrostEvent => (
+            "Frost Event",
+            get_frost_event_string(app),
+            match app.frost_events.last() {
+                Some(event) if event.end.is_none() => Severity::Warning,
+                _ => Severity::Ok,
+            },
         ),
     }
 }
 
-fn get_panel_door_values(app: &App) -> (String, String, String) {
-    let fan_data = &app.fan_data;
-    match fan_data {
-        Some(dta) => (
-            dta.get_main_switch_string(),
-            dta.get_main_panel_string(),
-            dta.get_control_door_string(),
-        ),
-        None => (
-            String::from("No Data"),
-            String::from("No Data"),
-            String::from("No Data"),
+// Current wind heading, or the wind rose of the history window
+fn draw_wind_compass<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect, title: &str, rose: bool) {
+    let direction = app
+        .fan_data
+        .as_ref()
+        .and_then(|dta| dta.wind_direction.value());
+    let compass = match rose {
+        true => Compass::new(direction, get_wind_rose_label(app)).rose(&app.wind_rose),
+        false => Compass::new(direction, get_wind_label(app)),
+    };
+    add_mouse_target(app, area, MouseTarget::Metric(&WIND_SPEED));
+    let compass = compass.block(render_block(title)).theme(app.config.theme);
+    f.render_widget(compass, area);
+}
+
+// e.g. 3m/s from 233° SW
+fn get_wind_label(app: &App) -> String {
+    match &app.fan_data {
+        Some(dta) => format!(
+            "{} from {}",
+            dta.get_wind_speed_string(),
+            dta.get_wind_direction_string()
         ),
+        None => String::from("No Data"),
     }
 }
 
-fn get_main_panel_values(app: &App) -> (String, String) {
-    let fan_data = &app.fan_data;
-    match fan_data {
-        Some(dta) => (
-            dta.get_operating_mode_string(),
-            dta.get_running_status_string(),
+// e.g. Mostly SW (34%), 5% calm
+fn get_wind_rose_label(app: &App) -> String {
+    let rose = &app.wind_rose;
+    let calm = match rose.total {
+        0 => return String::from("No Data"),
+        total => rose.calm * 100 / total,
+    };
+    match rose.get_prevailing_sector() {
+        Some(sector) => format!(
+            "Mostly {} ({:.0}%), {}% calm",
+            CARDINAL_DIRECTIONS[sector],
+            rose.get_frequency(sector) * 100.0,
+            calm
         ),
-        None => (String::from("No Data"), String::from("No Data")),
+        None => String::from("Calm"),
     }
 }
 
-fn get_block_content_chunks(chunk: Rect) -> Vec<Rect> {
-    Layout::default()
-        .direction(Direction::Vertical)
-        .margin(1)
-        .constraints(
-            [
-                Constraint::Max(0),
-                Constraint::Max(3),
-                Constraint::Max(3),
-                Constraint::Max(3),
-                Constraint::Max(0),
-            ]
-            .as_ref(),
-        )
-        .split(chunk)
+// Value block of one field. Bounded numbers fill a gauge with the value as its
+// label and trending numbers plot the history window as a sparkline, next to
+// the value or underneath it when the block is tall enough.
+fn draw_metric<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect, field: &'static FieldInfo) {
+    let severity = get_selected_severity(app, field);
+    let block = Block::default()
+        .style(get_border_style(app, severity))
+        .title(field.label)
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let (value, reading) = match &app.fan_data {
+        Some(dta) => match (field.value)(dta) {
+            FieldValue::Number(reading) => {
+                add_mouse_target(app, area, MouseTarget::Metric(field));
+                ((field.display)(dta), reading.value())
+            }
+            FieldValue::Text(_) => ((field.display)(dta), None),
+        },
+        None => (String::from("No Data"), None),
+    };
+    let history = match field.trend {
+        true => get_field_history(app, field),
+        false => Vec::new(),
+    };
+    let (value_area, trend_area) = match history.len() {
+        0 | 1 => (inner, None),
+        _ if inner.height >= 2 => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(1)].as_ref())
+                .split(inner);
+            (chunks[0], Some(chunks[1]))
+        }
+        _ if inner.width >= MIN_INLINE_SPARKLINE_WIDTH => {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(inner);
+            (chunks[0], Some(chunks[1]))
+        }
+        _ => (inner, None),
+    };
+
+    match (field.range, reading) {
+        (Some((min, max)), Some(reading)) => {
+            let ratio = ((reading - min) / (max - min)).clamp(0.0, 1.0);
+            let theme = &app.config.theme;
+            let gauge_color = get_border_style(app, severity).fg.unwrap_or(theme.border);
+            let gauge = Gauge::default()
+                .gauge_style(Style::default().fg(gauge_color).bg(theme.background))
+                .ratio(ratio as f64)
+                .label(value);
+            f.render_widget(gauge, value_area);
+        }
+        _ => {
+            let paragraph = Paragraph::new(value)
+                .style(get_severity_style(app, severity))
+                .alignment(Alignment::Center);
+            f.render_widget(paragraph, value_area);
+        }
+    }
+
+    if let Some(trend_area) = trend_area {
+        let data = get_sparkline_data(&history, trend_area.width as usize);
+        let sparkline = Sparkline::default()
+            .data(&data)
+            .max(SPARKLINE_MAX)
+            .style(Style::default().fg(app.config.theme.trend));
+        f.render_widget(sparkline, trend_area);
+    }
 }
 
-fn get_tab_content_chunks(chunk: Rect) -> (Vec<Rect>, Vec<Rect>, Vec<Rect>) {
-    // Splits chunks into 3 equal vertical portions
-    let horizontal_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .margin(0)
-        .constraints(
-            [
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-            ]
-            .as_ref(),
-        )
-        .split(chunk);
+// Valid readings of a numeric field in the history window, oldest first
+fn get_field_history(app: &App, field: &FieldInfo) -> Vec<f32> {
+    app.history
+        .iter()
+        .filter_map(|dta| match (field.value)(dta) {
+            FieldValue::Number(reading) => reading.value(),
+            FieldValue::Text(_) => None,
+        })
+        .collect()
+}
 
-    // Left Layout
-    let left_chunks = Layout::default()
+// Averages the values into one bar per column, scaled to the plotted values
+// so small changes of bounded fields still show. Bars start at SPARKLINE_MIN
+// so the lowest value isn't blank.
+fn get_sparkline_data(values: &[f32], width: usize) -> Vec<u64> {
+    if values.is_empty() || width == 0 {
+        return Vec::new();
+    }
+    let bucket_size = values.len().div_ceil(width);
+    let means: Vec<f32> = values
+        .chunks(bucket_size)
+        .map(|bucket| bucket.iter().sum::<f32>() / bucket.len() as f32)
+        .collect();
+    let (min, max) = means
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), value| {
+            (min.min(*value), max.max(*value))
+        });
+    means
+        .iter()
+        .map(|value| match max > min {
+            true => {
+                let ratio = ((value - min) / (max - min)).clamp(0.0, 1.0);
+                SPARKLINE_MIN + (ratio * (SPARKLINE_MAX - SPARKLINE_MIN) as f32).round() as u64
+            }
+            false => SPARKLINE_MAX / 2,
+        })
+        .collect()
+}
+
+fn draw_fan_maintenance<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let report = match &app.maintenance {
+        Some(report) => report,
+        None => {
+            let paragraph = Paragraph::new("No Data")
+                .style(Style::default().fg(app.config.theme.unknown))
+                .alignment(Alignment::Center)
+                .block(render_block("Maintenance"));
+            f.render_widget(paragraph, area);
+            return;
+        }
+    };
+    let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(0)
-        .constraints(
-            [
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-            ]
-            .as_ref(),
-        )
-        .split(horizontal_chunks[0]);
+        .constraints([Constraint::Length(7), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let header = Row::new(vec!["Period", "Run Hours", "Starts", "Longest Run"])
+        .style(Style::default().add_modifier(Modifier::BOLD))
+        .bottom_margin(1);
+    let widths = [
+        Constraint::Percentage(25),
+        Constraint::Percentage(25),
+        Constraint::Percentage(25),
+        Constraint::Percentage(25),
+    ];
+    let get_row = |period: String, stats: &RunStats| {
+        Row::new(vec![
+            period,
+            stats.get_run_hours_string(),
+            stats.starts.to_string(),
+            stats.get_longest_run_string(),
+        ])
+    };
 
-    // Middle Layout
-    let middle_chunks = Layout::default()
+    let totals = vec![
+        get_row(String::from("Today"), &report.today),
+        get_row(
+            format!("Season since {}", report.season_start),
+            &report.season,
+        ),
+        get_row(String::from("Lifetime"), &report.lifetime),
+    ];
+    let table = Table::new(totals)
+        .header(header.clone())
+        .block(render_block_owned(format!(
+            "Maintenance - {} device details",
+            app.config.keymap.get_hint(Action::Maintenance)
+        )))
+        .widths(&widths);
+    f.render_widget(table, chunks[0]);
+
+    let days = report
+        .days
+        .iter()
+        .map(|day| get_row(day.date.clone(), &day.stats))
+        .collect::<Vec<Row>>();
+    let table = Table::new(days)
+        .header(header)
+        .block(render_block_owned(format!(
+            "Last {} Days",
+            report.days.len()
+        )))
+        .widths(&widths);
+    f.render_widget(table, chunks[1]);
+}
+
+fn draw_history<B: Backend>(f: &mut Frame<B>, app: &App, area: Rect) {
+    let theme = &app.config.theme;
+    let chunks = Layout::default()
         .direction(Direction::Vertical)
         .margin(0)
         .constraints(
             [
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
+                Constraint::Percentage(50),
+                Constraint::Percentage(25),
+                Constraint::Percentage(25),
             ]
             .as_ref(),
         )
-        .split(horizontal_chunks[1]);
+        .split(area);
 
-    // Right Layout
-    let right_chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(0)
-        .constraints(
-            [
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-                Constraint::Percentage(33),
-            ]
-            .as_ref(),
+    // X axis is seconds since the start of the history window
+    let window_seconds = app.history_window.duration().num_seconds() as f64;
+    let window_start = chrono::offset::Local::now().naive_local() - app.history_window.duration();
+    let x_labels = vec![
+        Span::raw(window_start.format("%d/%m %H:%M").to_string()),
+        Span::raw(format!("Last {}", app.history_window.label())),
+        Span::raw("Now"),
+    ];
+    let history_points = |reading: &dyn Fn(&FanData) -> Reading<f32>| -> Vec<(f64, f64)> {
+        let mut points = Vec::new();
+        for fan_data in &app.history {
+            let value = match reading(fan_data).value() {
+                Some(value) => value,
+                None => continue,
+            };
+            if let Ok(datetime) = fan_data.get_last_update_datetime() {
+                let x = (datetime - window_start).num_seconds() as f64;
+                points.push((x, value as f64));
+            }
+        }
+        points
+    };
+    let window_title = |title: &str| {
+        format!(
+            "{} - Last {} - {} change window",
+            title,
+            app.history_window.label(),
+            app.config.keymap.get_hint(Action::HistoryWindow)
         )
-        .split(horizontal_chunks[2]);
+    };
+
+    // A field picked from the detail view gets the whole view
+    if let Some(field) = app.history_field {
+        let points = history_points(&|d| match (field.value)(d) {
+            FieldValue::Number(reading) => reading,
+            FieldValue::Text(_) => Reading::Missing,
+        });
+        let title = format!(
+            "{} - {} device details",
+            window_title(&field.get_column_name()),
+            app.config.keymap.get_hint(Action::History)
+        );
+        let series = vec![(field.label, theme.series[0], points.as_slice())];
+        let chart = render_history_chart(title, series, window_seconds, x_labels, theme);
+        f.render_widget(chart, area);
+        add_mouse_target(app, area, MouseTarget::Chart);
+        return;
+    }
 
-    (left_chunks, middle_chunks, right_chunks)
+    // Top chart, then the two smaller charts below it
+    let temperature_top = history_points(&|d| d.temperature_top);
+    let (top_chart, middle_chart, bottom_chart) = match get_selected_layout(app) {
+        DeviceLayout::Fan => (
+            (
+                window_title("Temperature (℃)"),
+                vec![
+                    ("Top", theme.series[0], temperature_top),
+                    (
+                        "Bottom",
+                        theme.series[1],
+                        history_points(&|d| d.temperature_bottom),
+                    ),
+                    (
+                        "Far",
+                        theme.series[2],
+                        history_points(&|d| d.temperature_far),
+                    ),
+                ],
+            ),
+            (
+                String::from("Motor Current (A)"),
+                vec![(
+                    "Current",
+                    theme.series[3],
+                    history_points(&|d| d.motor_current),
+                )],
+            ),
+            (
+                String::from("Motor Vibration (mm/s)"),
+                vec![(
+                    "Vibration",
+                    theme.series[4],
+                    history_points(&|d| d.motor_vibration),
+                )],
+            ),
+        ),
+        DeviceLayout::WeatherStation => (
+            (
+                window_title("Air Temperature (℃)"),
+                vec![("Air", theme.series[0], temperature_top)],
+            ),
+            (
+                String::from("Humidity (%)"),
+                vec![("Humidity", theme.series[3], history_points(&|d| d.humidity))],
+            ),
+            (
+                String::from("Wind Speed (m/s)"),
+                vec![("Wind", theme.series[4], history_points(&|d| d.wind_speed))],
+            ),
+        ),
+        DeviceLayout::TemperatureProbe => (
+            (
+                window_title("Temperature (℃)"),
+                vec![
+                    ("Top", theme.series[0], temperature_top),
+                    (
+                        "Bottom",
+                        theme.series[1],
+                        history_points(&|d| d.temperature_bottom),
+                    ),
+                    (
+                        "Far",
+                        theme.series[2],
+                        history_points(&|d| d.temperature_far),
+                    ),
+                ],
+            ),
+            (
+                String::from("Battery Voltage (V)"),
+                vec![(
+                    "Battery",
+                    theme.series[3],
+                    history_points(&|d| d.battery_voltage),
+                )],
+            ),
+            (
+                String::from("Signal Strength (dBm)"),
+                vec![(
+                    "Signal",
+                    theme.series[4],
+                    history_points(&|d| d.signal_strength),
+                )],
+            ),
+        ),
+    };
+
+    for ((title, lines), chunk) in [top_chart, middle_chart, bottom_chart].iter().zip(chunks) {
+        let series = lines
+            .iter()
+            .map(|(name, color, points)| (*name, *color, points.as_slice()))
+            .collect();
+        let chart = render_history_chart(
+            title.clone(),
+            series,
+            window_seconds,
+            x_labels.clone(),
+            theme,
+        );
+        f.render_widget(chart, chunk);
+        add_mouse_target(app, chunk, MouseTarget::Chart);
+    }
+}
+
+// Detail and history layout of the selected device
+fn get_selected_layout(app: &App) -> DeviceLayout {
+    match app.devices.get(app.index) {
+        Some(device) => device.device_type.layout,
+        None => DeviceLayout::Fan,
+    }
+}
+
+// Legend name, line color and (x, y) points of one chart line
+type ChartSeries<'a> = (&'a str, Color, &'a [(f64, f64)]);
+
+fn render_history_chart<'a>(
+    title: String,
+    series: Vec<ChartSeries<'a>>,
+    window_seconds: f64,
+    x_labels: Vec<Span<'a>>,
+    theme: &Theme,
+) -> Chart<'a> {
+    // Fit the y axis to the plotted values with a little padding
+    let mut min = f64::MAX;
+    let mut max = f64::MIN;
+    for (_, _, points) in &series {
+        for (_, y) in points.iter() {
+            min = min.min(*y);
+            max = max.max(*y);
+        }
+    }
+    if min > max {
+        min = 0.0;
+        max = 1.0;
+    }
+    let padding = ((max - min) * 0.1).max(0.5);
+    let (min, max) = (min - padding, max + padding);
+
+    let datasets = series
+        .into_iter()
+        .map(|(name, color, points)| {
+            Dataset::default()
+                .name(name)
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(color))
+                .data(points)
+        })
+        .collect();
+
+    Chart::new(datasets)
+        .block(render_block_owned(title))
+        .hidden_legend_constraints((Constraint::Ratio(1, 4), Constraint::Ratio(1, 2)))
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(theme.axis))
+                .bounds([0.0, window_seconds])
+                .labels(x_labels),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(theme.axis))
+                .bounds([min, max])
+                .labels(vec![
+                    Span::raw(format!("{:.1}", min)),
+                    Span::raw(format!("{:.1}", (min + max) / 2.0)),
+                    Span::raw(format!("{:.1}", max)),
+                ]),
+        )
+}
+
+// Remembers where a widget was drawn so mouse events can find it
+fn add_mouse_target(app: &App, area: Rect, target: MouseTarget) {
+    app.mouse_targets.borrow_mut().push((area, target));
+}
+
+fn render_block(title: &str) -> Block<'_> {
+    Block::default().title(title).borders(Borders::ALL)
+}
+
+fn render_block_owned<'a>(title: String) -> Block<'a> {
+    Block::default().title(title).borders(Borders::ALL)
+}
+
+fn render_block_with_content<'a>(
+    app: &App,
+    title: &'a str,
+    content: &'a str,
+    severity: Severity,
+) -> Paragraph<'a> {
+    let block = Block::default()
+        .style(get_border_style(app, severity))
+        .title(title)
+        .borders(Borders::ALL);
+    let paragraph = Paragraph::new(content)
+        .block(block)
+        .style(get_severity_style(app, severity))
+        .alignment(Alignment::Center);
+    paragraph
+}
+
+// Most recent frost event, the history is ordered oldest first
+fn get_frost_event_string(app: &App) -> String {
+    let format_time =
+        |datetime: &str| match NaiveDateTime::parse_from_str(datetime, DATETIME_FORMAT) {
+            Ok(datetime) => datetime.format("%d/%m %H:%M").to_string(),
+            Err(_) => datetime.to_string(),
+        };
+    match app.frost_events.last() {
+        Some(event) => match &event.end {
+            Some(end) => format!(
+                "{} - {} (min {}℃)",
+                format_time(&event.start),
+                format_time(end),
+                event.min_temperature
+            ),
+            None => format!(
+                "Since {} (min {}℃)",
+                format_time(&event.start),
+                event.min_temperature
+            ),
+        },
+        None => format!("None below {}℃", app.frost_threshold),
+    }
+}
+
+// Values take three rows each, compasses share whatever is left
+fn get_panel_item_chunks(chunk: Rect, items: &[PanelItem]) -> Vec<Rect> {
+    let is_compass = |item: &PanelItem| matches!(item, PanelItem::Wind | PanelItem::WindRose);
+    let mut constraints: Vec<Constraint> = items
+        .iter()
+        .map(|item| match is_compass(item) {
+            true => Constraint::Min(3),
+            false => Constraint::Length(3),
+        })
+        .collect();
+    if !items.iter().any(is_compass) {
+        constraints.push(Constraint::Min(0));
+    }
+    Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(constraints)
+        .split(chunk)
+}
+
+// Equally sized cells of a grid, row by row
+fn get_panel_chunks(chunk: Rect, rows: usize, columns: usize) -> Vec<Rect> {
+    let row_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(0)
+        .constraints(vec![Constraint::Ratio(1, rows.max(1) as u32); rows.max(1)])
+        .split(chunk);
+    row_chunks
+        .into_iter()
+        .flat_map(|row| {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .margin(0)
+                .constraints(vec![Constraint::Ratio(1, columns as u32); columns])
+                .split(row)
+        })
+        .collect()
 }
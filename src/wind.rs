@@ -0,0 +1,167 @@
+use crate::fan_data::FanData;
+
+// Cardinal labels clockwise from north, each covering 45°
+pub const CARDINAL_DIRECTIONS: [&str; 8] = ["N", "NE", "E", "SE", "S", "SW", "W", "NW"];
+
+// Readings below this wind speed (m/s) have no meaningful direction
+const CALM_WIND_SPEED: f32 = 0.5;
+
+// Index into CARDINAL_DIRECTIONS of the sector a heading falls in
+pub fn get_sector(degrees: i16) -> usize {
+    let degrees = (degrees as i32).rem_euclid(360);
+    (((degrees as f32 + 22.5) / 45.0) as usize) % CARDINAL_DIRECTIONS.len()
+}
+
+pub fn get_cardinal_direction(degrees: i16) -> &'static str {
+    CARDINAL_DIRECTIONS[get_sector(degrees)]
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WindSector {
+    pub count: usize,
+    pub total_speed: f32,
+    pub max_speed: f32,
+}
+
+impl WindSector {
+    pub fn get_mean_speed(&self) -> f32 {
+        match self.count {
+            0 => 0.0,
+            count => self.total_speed / count as f32,
+        }
+    }
+}
+
+// Wind speed and direction readings binned by the direction the wind came from
+#[derive(Clone, Debug, Default)]
+pub struct WindRose {
+    pub sectors: [WindSector; 8],
+    // Readings below CALM_WIND_SPEED
+    pub calm: usize,
+    pub total: usize,
+}
+
+impl WindRose {
+    // Share of all readings that fell in a sector
+    pub fn get_frequency(&self, sector: usize) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => self.sectors[sector].count as f64 / total as f64,
+        }
+    }
+
+    // Sector the wind came from most often, None without any non-calm readings
+    pub fn get_prevailing_sector(&self) -> Option<usize> {
+        (0..self.sectors.len())
+            .filter(|sector| self.sectors[*sector].count > 0)
+            .max_by_key(|sector| self.sectors[*sector].count)
+    }
+}
+
+// Readings without both a valid speed and direction are left out
+pub fn get_wind_rose(history: &[FanData]) -> WindRose {
+    let mut rose = WindRose::default();
+    for fan_data in history {
+        let (speed, direction) =
+            match (fan_data.wind_speed.value(), fan_data.wind_direction.value()) {
+                (Some(speed), Some(direction)) => (speed, direction),
+                _ => continue,
+            };
+        rose.total += 1;
+        if speed < CALM_WIND_SPEED {
+            rose.calm += 1;
+            continue;
+        }
+        let sector = &mut rose.sectors[get_sector(direction)];
+        sector.count += 1;
+        sector.total_speed += speed;
+        sector.max_speed = sector.max_speed.max(speed);
+    }
+    rose
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fan_data::Reading;
+
+    fn get_reading(speed: Reading<f32>, direction: Reading<i16>) -> FanData {
+        FanData {
+            wind_speed: speed,
+            wind_direction: direction,
+            ..FanData::new(String::from("2024-05-01 00:00:00"))
+        }
+    }
+
+    #[test]
+    fn sectors_are_centered_on_their_heading() {
+        assert_eq!(get_cardinal_direction(0), "N");
+        assert_eq!(get_cardinal_direction(22), "N");
+        assert_eq!(get_cardinal_direction(23), "NE");
+        assert_eq!(get_cardinal_direction(90), "E");
+        assert_eq!(get_cardinal_direction(180), "S");
+        assert_eq!(get_cardinal_direction(292), "W");
+        assert_eq!(get_cardinal_direction(293), "NW");
+    }
+
+    #[test]
+    fn sectors_wrap_around_north() {
+        assert_eq!(get_sector(337), 7);
+        assert_eq!(get_sector(338), 0);
+        assert_eq!(get_sector(350), 0);
+        assert_eq!(get_sector(359), 0);
+        assert_eq!(get_sector(360), 0);
+        assert_eq!(get_sector(405), 1);
+        assert_eq!(get_sector(-10), 0);
+        assert_eq!(get_sector(-90), 6);
+    }
+
+    #[test]
+    fn rose_bins_readings_by_sector() {
+        let history = [
+            get_reading(Reading::Ok(2.0), Reading::Ok(350)),
+            get_reading(Reading::Ok(4.0), Reading::Ok(10)),
+            get_reading(Reading::Ok(6.0), Reading::Ok(180)),
+            get_reading(Reading::Ok(1.0), Reading::Ok(360)),
+        ];
+        let rose = get_wind_rose(&history);
+        assert_eq!(rose.total, 4);
+        assert_eq!(rose.calm, 0);
+        assert_eq!(rose.sectors[0].count, 3);
+        assert_eq!(rose.sectors[0].get_mean_speed(), 7.0 / 3.0);
+        assert_eq!(rose.sectors[0].max_speed, 4.0);
+        assert_eq!(rose.sectors[4].count, 1);
+        assert_eq!(rose.get_frequency(0), 0.75);
+        assert_eq!(rose.get_prevailing_sector(), Some(0));
+    }
+
+    #[test]
+    fn calm_readings_count_without_a_sector() {
+        let history = [
+            get_reading(Reading::Ok(0.4), Reading::Ok(90)),
+            get_reading(Reading::Ok(0.0), Reading::Ok(0)),
+            get_reading(Reading::Ok(0.5), Reading::Ok(90)),
+        ];
+        let rose = get_wind_rose(&history);
+        assert_eq!(rose.total, 3);
+        assert_eq!(rose.calm, 2);
+        assert_eq!(rose.sectors[2].count, 1);
+        assert_eq!(rose.sectors[0].count, 0);
+        assert_eq!(rose.sectors[0].get_mean_speed(), 0.0);
+    }
+
+    #[test]
+    fn readings_without_speed_or_direction_are_left_out() {
+        let history = [
+            get_reading(Reading::Missing, Reading::Ok(90)),
+            get_reading(Reading::Ok(3.0), Reading::Missing),
+            get_reading(Reading::Error, Reading::Disabled),
+        ];
+        let rose = get_wind_rose(&history);
+        assert_eq!(rose.total, 0);
+        assert_eq!(rose.get_frequency(0), 0.0);
+        assert_eq!(rose.get_prevailing_sector(), None);
+        let calm = get_wind_rose(&[get_reading(Reading::Ok(0.1), Reading::Ok(90))]);
+        assert_eq!(calm.get_prevailing_sector(), None);
+    }
+}
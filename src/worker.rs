@@ -0,0 +1,362 @@
+use crate::device::DeviceType;
+use crate::export::{export_history, ExportFormat};
+use crate::fan_data::FanData;
+use crate::maintenance::{get_maintenance_report, MaintenanceReport, RunningHistory, SeasonStart};
+use crate::sqlite::{DataVersion, Database, Device, LastRow};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often the worker asks SQLite whether anything was written
+const CHANGE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Queries the UI hands to the worker thread. Requests about one device carry its
+// FanN table so responses for a device that is no longer selected can be dropped,
+// and its type when the rows are decoded.
+pub enum Request {
+    Overview {
+        devices: Vec<Device>,
+    },
+    FanData {
+        table: String,
+        device_type: &'static DeviceType,
+    },
+    History {
+        table: String,
+        device_type: &'static DeviceType,
+        since: String,
+    },
+    Maintenance {
+        table: String,
+        days: usize,
+        season_start: SeasonStart,
+    },
+    ExportHistory {
+        device: Device,
+        since: String,
+        path: PathBuf,
+        format: ExportFormat,
+    },
+}
+
+pub enum Response {
+    Overview {
+        overview: Vec<Option<FanData>>,
+        // Last error of any device, prefixed with the device name
+        error: Option<String>,
+    },
+    FanData {
+        table: String,
+        result: Result<Option<FanData>, rusqlite::Error>,
+    },
+    History {
+        table: String,
+        result: Result<Vec<FanData>, rusqlite::Error>,
+    },
+    Maintenance {
+        table: String,
+        result: Result<MaintenanceReport, rusqlite::Error>,
+    },
+    // Path and number of rows written
+    Export(Result<(PathBuf, usize), String>),
+    // Sent without a request when new rows were written, with the latest
+    // reading of every FanN table that changed
+    Changed {
+        updates: Vec<(String, Result<Option<FanData>, rusqlite::Error>)>,
+    },
+}
+
+impl Response {
+    // Whether the response answers a request, as opposed to a change notification
+    pub fn is_reply(&self) -> bool {
+        !matches!(self, Response::Changed { .. })
+    }
+}
+
+// Handle to the thread that owns the database connection
+pub struct Worker {
+    requests: Sender<Request>,
+    responses: Receiver<Response>,
+}
+
+impl Worker {
+    pub fn spawn(database: Database) -> Worker {
+        let (request_sender, request_receiver) = mpsc::channel();
+        let (response_sender, response_receiver) = mpsc::channel();
+        thread::spawn(move || run(database, request_receiver, response_sender));
+        Worker {
+            requests: request_sender,
+            responses: response_receiver,
+        }
+    }
+
+    // Returns false when the worker thread is gone and no response will come
+    pub fn send(&self, request: Request) -> bool {
+        self.requests.send(request).is_ok()
+    }
+
+    pub fn try_recv(&self) -> Option<Response> {
+        self.responses.try_recv().ok()
+    }
+}
+
+// Tables of the devices the App knows about and what they looked like when last checked
+#[derive(Default)]
+struct ChangeWatcher {
+    tables: Vec<(String, &'static DeviceType)>,
+    data_version: Option<DataVersion>,
+    last_rows: HashMap<String, Option<LastRow>>,
+}
+
+impl ChangeWatcher {
+    fn watch(&mut self, database: &Database, devices: &[Device]) {
+        self.tables = devices
+            .iter()
+            .filter_map(|device| Some((device.table.clone()?, device.device_type)))
+            .collect();
+        self.data_version = database.get_data_version().ok();
+        self.last_rows = self
+            .tables
+            .iter()
+            .map(|(table, _)| (table.clone(), database.get_last_row(table).ok().flatten()))
+            .collect();
+    }
+
+    // Tables that got new rows or had their newest row rewritten since the
+    // last check. Writes to other tables, e.g. devices, change none of them.
+    fn get_changed_tables(&mut self, database: &Database) -> Vec<(String, &'static DeviceType)> {
+        let data_version = match database.get_data_version() {
+            Ok(data_version) => data_version,
+            Err(_) => return Vec::new(),
+        };
+        if self.data_version == Some(data_version) {
+            return Vec::new();
+        }
+        self.data_version = Some(data_version);
+        let mut changed = Vec::new();
+        for (table, device_type) in &self.tables {
+            let last_row = database.get_last_row(table).ok().flatten();
+            if self.last_rows.get(table) != Some(&last_row) {
+                self.last_rows.insert(table.clone(), last_row);
+                changed.push((table.clone(), *device_type));
+            }
+        }
+        changed
+    }
+}
+
+// Runs until the App drops its Worker, checking for new rows between requests
+fn run(database: Database, requests: Receiver<Request>, responses: Sender<Response>) {
+    let mut watcher = ChangeWatcher::default();
+    // Running samples of every fan whose run hours were asked for
+    let mut running_histories = HashMap::new();
+    let mut last_check = Instant::now();
+    loop {
+        let response = match requests.recv_timeout(CHANGE_POLL_INTERVAL) {
+            Ok(request) => {
+                // Rows written after this point show up as changes
+                if let Request::Overview { devices } = &request {
+                    watcher.watch(&database, devices);
+                }
+                Some(handle_request(&database, &mut running_histories, request))
+            }
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if let Some(response) = response {
+            if responses.send(response).is_err() {
+                break;
+            }
+        }
+        if last_check.elapsed() < CHANGE_POLL_INTERVAL {
+            continue;
+        }
+        last_check = Instant::now();
+        let updates: Vec<_> = watcher
+            .get_changed_tables(&database)
+            .into_iter()
+            .map(|(table, device_type)| {
+                let result = database.get_last_fan_data(&table, device_type);
+                (table, result)
+            })
+            .collect();
+        if !updates.is_empty() && responses.send(Response::Changed { updates }).is_err() {
+            break;
+        }
+    }
+}
+
+fn handle_request(
+    database: &Database,
+    running_histories: &mut HashMap<String, RunningHistory>,
+    request: Request,
+) -> Response {
+    match request {
+        Request::Overview { devices } => {
+            let mut overview = Vec::new();
+            let mut error = None;
+            for device in &devices {
+                let fan_data = match &device.table {
+                    Some(table) => match database.get_last_fan_data(table, device.device_type) {
+                        Ok(data) => data,
+                        Err(query_error) => {
+                            error = Some(format!("{}: {}", device.get_name(), query_error));
+                            None
+                        }
+                    },
+                    None => None,
+                };
+                overview.push(fan_data);
+            }
+            Response::Overview { overview, error }
+        }
+        Request::FanData { table, device_type } => {
+            let result = database.get_last_fan_data(&table, device_type);
+            Response::FanData { table, result }
+        }
+        Request::History {
+            table,
+            device_type,
+            since,
+        } => {
+            let result = database.get_fan_data_history(&table, device_type, &since);
+            Response::History { table, result }
+        }
+        Request::Maintenance {
+            table,
+            days,
+            season_start,
+        } => {
+            let now = chrono::offset::Local::now().naive_local();
+            let history = running_histories.entry(table.clone()).or_default();
+            let result = database
+                .update_running_history(&table, history)
+                .map(|_| get_maintenance_report(&history.samples, now, days, season_start));
+            Response::Maintenance { table, result }
+        }
+        Request::ExportHistory {
+            device,
+            since,
+            path,
+            format,
+        } => {
+            let result = match &device.table {
+                Some(table) => database
+                    .get_fan_data_history(table, device.device_type, &since)
+                    .map_err(|error| error.to_string())
+                    .and_then(|history| {
+                        export_history(&path, &device, &history, format)
+                            .map_err(|error| error.to_string())
+                    })
+                    .map(|rows| (path, rows)),
+                None => Err(device.get_table_error()),
+            };
+            Response::Export(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use std::fs;
+
+    // Database file of its own, written through the returned connection while
+    // the Database reads it like the homebase service's file
+    fn create_database(name: &str) -> (String, Connection) {
+        let path = std::env::temp_dir()
+            .join(format!("agi-tui-test-{}-{}.db", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned();
+        let _ = fs::remove_file(&path);
+        let writer = Connection::open(&path).unwrap();
+        writer
+            .execute_batch(
+                "CREATE TABLE devices(sn TEXT, name TEXT, type INTEGER);
+                 INSERT INTO devices VALUES ('SN0001', 'North 1', 1);
+                 INSERT INTO devices VALUES ('SN0002', 'North 2', 1);
+                 CREATE TABLE Fan1(datetime TEXT);
+                 CREATE TABLE Fan2(datetime TEXT);
+                 INSERT INTO Fan1 VALUES ('2024-05-01 00:00:00');
+                 INSERT INTO Fan2 VALUES ('2024-05-01 00:00:00');",
+            )
+            .unwrap();
+        (path, writer)
+    }
+
+    fn watch(database: &Database) -> ChangeWatcher {
+        let mut watcher = ChangeWatcher::default();
+        watcher.watch(database, &database.get_devices().unwrap());
+        watcher
+    }
+
+    fn get_changed(watcher: &mut ChangeWatcher, database: &Database) -> Vec<String> {
+        watcher
+            .get_changed_tables(database)
+            .into_iter()
+            .map(|(table, _)| table)
+            .collect()
+    }
+
+    #[test]
+    fn new_rows_refresh_only_their_table() {
+        let (path, writer) = create_database("new-rows");
+        let database = Database::new(&path).unwrap();
+        let mut watcher = watch(&database);
+        assert!(get_changed(&mut watcher, &database).is_empty());
+
+        writer
+            .execute("INSERT INTO Fan2 VALUES ('2024-05-01 00:01:00')", [])
+            .unwrap();
+        assert_eq!(get_changed(&mut watcher, &database), vec!["Fan2"]);
+        assert!(get_changed(&mut watcher, &database).is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rewritten_newest_rows_refresh_their_table() {
+        let (path, writer) = create_database("rewritten");
+        let database = Database::new(&path).unwrap();
+        let mut watcher = watch(&database);
+        writer
+            .execute("UPDATE Fan1 SET datetime = '2024-05-01 00:02:00'", [])
+            .unwrap();
+        assert_eq!(get_changed(&mut watcher, &database), vec!["Fan1"]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn writes_to_other_tables_refresh_nothing() {
+        let (path, writer) = create_database("other-tables");
+        let database = Database::new(&path).unwrap();
+        let mut watcher = watch(&database);
+        writer
+            .execute("INSERT INTO devices VALUES ('SN0003', 'North 3', 1)", [])
+            .unwrap();
+        assert!(get_changed(&mut watcher, &database).is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    // Other platforms can't tell replaced files apart, see get_file_id
+    #[cfg(unix)]
+    #[test]
+    fn replaced_files_are_reopened() {
+        let (path, _writer) = create_database("replaced");
+        let database = Database::new(&path).unwrap();
+        let mut watcher = watch(&database);
+
+        // A new file renamed over the old one, as a restored backup would be
+        let (replacement, writer) = create_database("replacement");
+        writer
+            .execute("INSERT INTO Fan1 VALUES ('2024-05-01 00:05:00')", [])
+            .unwrap();
+        drop(writer);
+        fs::rename(&replacement, &path).unwrap();
+        assert_eq!(get_changed(&mut watcher, &database), vec!["Fan1"]);
+        assert!(get_changed(&mut watcher, &database).is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+}